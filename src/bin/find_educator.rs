@@ -0,0 +1,72 @@
+use lib::http_client::build_http_client;
+use lib::tt_diff::helpers::{get_educator_events_by_id, local_date, week_monday};
+use lib::tt_diff::models;
+use lib::tt_diff::rate_limiter::RateLimiter;
+use lib::tt_diff::schedule_getter::ScheduleGetter;
+use lib::tt_diff::schema_drift::SchemaChecker;
+
+use std::error::Error;
+
+use chrono::Utc;
+use clap::Parser;
+use figment::{
+    providers::{Env, Format, Json},
+    Figment,
+};
+use models::{Config, FindEducatorArgs};
+
+#[tokio::main]
+async fn main() {
+    env_logger::builder()
+        .target(env_logger::Target::Stderr)
+        .filter_level(log::LevelFilter::Warn)
+        .init();
+    let args = FindEducatorArgs::parse();
+    if let Err(error) = find_educators(&args).await {
+        eprintln!("Couldn't find educators: {}", error);
+        std::process::exit(1);
+    }
+}
+
+/// Prints ids of educators whose name starts with `args.name`
+async fn find_educators(args: &FindEducatorArgs) -> Result<(), Box<dyn Error>> {
+    let config: Config = Figment::new()
+        .merge(Json::file(&args.config_json_path))
+        .merge(Env::prefixed("TT_"))
+        .extract()?;
+
+    let http_client = build_http_client(&config.http)?;
+    let candidates = http_client.search_educators(&args.name, &config).await?;
+    if candidates.is_empty() {
+        println!("No educators found by name \"{}\"", args.name);
+        return Ok(());
+    }
+    if candidates.len() > 1 {
+        println!(
+            "Found {} educators, put the id of the right one into watch_educators:",
+            candidates.len()
+        );
+    }
+
+    /* long display text with positions is only a part of educator's events */
+    let rate_limiter = RateLimiter::new(config.requests_per_second);
+    let schema_checker = SchemaChecker::new(config.lenient_parsing);
+    let week = week_monday(local_date(&config, Utc::now()));
+    for candidate in candidates {
+        let display_text = match get_educator_events_by_id(
+            &http_client,
+            &rate_limiter,
+            &schema_checker,
+            &config.api_base_url,
+            candidate.id,
+            week,
+        )
+        .await
+        {
+            Ok((_, events)) => events.educator_long_display_text,
+            Err(_) => candidate.full_name,
+        };
+        println!("{}\t{}", candidate.id, display_text);
+    }
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use lettre::Transport;
+use lib::http_client::build_http_client;
 use lib::pdf_diff::helpers::{
     fetch_and_hash_pdf, generate_email, get_tables, get_users, write_updated_table_hashes,
 };
@@ -41,7 +42,7 @@ async fn main() {
     );
 
     /* Get tables and find changed ones */
-    let http_client = reqwest::Client::new();
+    let http_client = build_http_client(&config.http).unwrap();
     let tables: Vec<Table> = get_tables(&args).unwrap();
     let mut changed_tables = Vec::<(String, String)>::new();
     for table in &tables {
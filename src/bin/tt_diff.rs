@@ -1,6 +1,8 @@
+use lib::http_client::build_http_client;
 use lib::tt_diff::models;
 use lib::tt_diff::run_tool::run;
 
+use chrono::Utc;
 use clap::Parser;
 use figment::{
     providers::{Env, Format, Json},
@@ -32,7 +34,7 @@ async fn main() {
             .display()
     );
 
-    let http_client = reqwest::Client::new();
+    let http_client = build_http_client(&config.http).unwrap();
     let sender = SmtpTransport::relay(&config.email_relay)
         .unwrap()
         .credentials(Credentials::new(
@@ -41,5 +43,5 @@ async fn main() {
         ))
         .authentication(vec![Mechanism::Plain])
         .build();
-    run(http_client, sender, &args, config).await;
+    run(http_client, sender, &args, config, Utc::now()).await;
 }
@@ -0,0 +1,18 @@
+//! Escaping of text put into HTML letters
+
+/// `text` with `&`, `<`, `>` and quotes replaced by entities.
+/// Unlike most escapers leaves `/` alone, so addresses like "д. 6/8" stay readable
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
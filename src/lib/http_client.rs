@@ -0,0 +1,61 @@
+//! HTTP client settings shared by both tools
+use std::{collections::BTreeMap, error::Error, time::Duration};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, Proxy,
+};
+use serde::{Deserialize, Serialize};
+
+/// Part of `config.json`, every field is optional
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HttpSettings {
+    /// Whole request timeout, so that a stalled server can't hang the run
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String,
+    /// Proxy for all requests, e.g. `http://proxy.example.com:3128`
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Headers added to every request
+    #[serde(default)]
+    pub extra_headers: BTreeMap<String, String>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        HttpSettings {
+            request_timeout_secs: default_request_timeout_secs(),
+            user_agent: default_user_agent(),
+            proxy: None,
+            extra_headers: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_request_timeout_secs() -> u64 {
+    30
+}
+
+fn default_user_agent() -> String {
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string()
+}
+
+pub fn build_http_client(settings: &HttpSettings) -> Result<Client, Box<dyn Error>> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in settings.extra_headers.iter() {
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(value)?,
+        );
+    }
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(settings.request_timeout_secs))
+        .user_agent(&settings.user_agent)
+        .default_headers(headers);
+    if let Some(proxy) = &settings.proxy {
+        builder = builder.proxy(Proxy::all(proxy)?);
+    }
+    Ok(builder.build()?)
+}
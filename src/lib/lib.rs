@@ -1,2 +1,5 @@
+pub mod html;
+pub mod http_client;
+pub mod locale;
 pub mod pdf_diff;
 pub mod tt_diff;
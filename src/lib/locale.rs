@@ -0,0 +1,91 @@
+//! Languages of letters and the catalogue of their texts
+use serde::{Deserialize, Serialize};
+
+/// Language a user gets letters in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    Ru,
+    En,
+}
+
+impl Locale {
+    /// Text of `key` in this language, the key itself if the catalogue lacks it.
+    /// Placeholders like `{name}` are filled by the caller
+    pub fn tr(self, key: &str) -> &str {
+        let (ru, en) = match key {
+            "dear" => ("Уважаемый(ая)", "Dear"),
+            "auto_generated" => (
+                "Данное письмо было сгенерировано автоматически, направление ответа не подразумевается.",
+                "This letter was generated automatically, please do not reply to it.",
+            ),
+            "changes_subject" => ("Изменилось расписание!", "Your schedule has changed!"),
+            "welcome_subject" => (
+                "Вы подписались на расписание",
+                "You have subscribed to a schedule",
+            ),
+            "subscribed" => (
+                "Вы подписались на оповещения об изменениях расписания, сейчас оно выглядит так:",
+                "You have subscribed to notifications about schedule changes, this is how it looks now:",
+            ),
+            "schedule_changed" => (
+                "В расписании {whose} {name}{week} произошли изменения:",
+                "The schedule of {whose} {name}{week} has changed:",
+            ),
+            "new_semester" => (
+                "Опубликовано расписание {whose} {name}{week} на новый семестр.",
+                "The schedule of {whose} {name}{week} for the new semester has been published.",
+            ),
+            "current_schedule" => ("Расписание {whose} {name}{week}:", "Schedule of {whose} {name}{week}:"),
+            "week" => (" на неделе {monday}–{sunday}", " for the week {monday}–{sunday}"),
+            "whose_educator" => ("преподавателя", "educator"),
+            "whose_group" => ("группы", "group"),
+            "whose_location" => ("аудитории", "classroom"),
+            "no_events" => ("Занятий нет.", "No classes."),
+            "subject" => ("Предмет", "Subject"),
+            "time" => ("Время", "Time"),
+            "dates" => ("Даты", "Dates"),
+            "locations" => ("Места", "Locations"),
+            "educators" => ("Преподаватели", "Educators"),
+            "groups" => ("Направления", "Groups"),
+            "dates_added" => ("добавлены {dates}", "added {dates}"),
+            "dates_removed" => ("удалены {dates}", "removed {dates}"),
+            "cancelled" => ("Отменено", "Cancelled"),
+            "modified_events" => ("Изменённые события", "Changed events"),
+            "reassigned" => ("Замена: {from} → {to}", "Substitution: {from} → {to}"),
+            "added_events" => ("Новые события", "New events"),
+            "removed_events" => ("Удалённые события", "Removed events"),
+            "new_day" => ("Новый день", "New day"),
+            "time_was_changed" => (
+                "Время изменено учебным отделом",
+                "Time changed by the dean's office",
+            ),
+            "locations_were_changed" => (
+                "Аудитория изменена учебным отделом",
+                "Classroom changed by the dean's office",
+            ),
+            "educators_were_reassigned" => (
+                "Преподаватели заменены учебным отделом",
+                "Educators replaced by the dean's office",
+            ),
+            "monday" => ("Понедельник", "Monday"),
+            "tuesday" => ("Вторник", "Tuesday"),
+            "wednesday" => ("Среда", "Wednesday"),
+            "thursday" => ("Четверг", "Thursday"),
+            "friday" => ("Пятница", "Friday"),
+            "saturday" => ("Суббота", "Saturday"),
+            "sunday" => ("Воскресенье", "Sunday"),
+            "tables_subject" => ("Обновление таблиц", "Schedule tables updated"),
+            "tables_changed" => (
+                "Следующие таблицы с расписаниями, которые вы отслеживаете, были изменены:",
+                "The following schedule tables you watch have changed:",
+            ),
+            _ => return key,
+        };
+        match self {
+            Locale::Ru => ru,
+            Locale::En => en,
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use lettre::{message::header::ContentType, Message};
+use lettre::{message::MultiPart, Message};
 use log::{debug, info};
 use reqwest::Client;
 use sha2::{Digest, Sha256};
@@ -9,9 +9,10 @@ use std::{
     path::Path,
 };
 
+use crate::html;
 use crate::pdf_diff::models::{Args, Config, Table, User};
 
-pub fn log_all_users(users: &[User]) -> () {
+pub fn log_all_users(users: &[User]) {
     //println!("length: {:?}", users.len());
     for user in users.iter() {
         debug!(
@@ -34,7 +35,7 @@ pub fn get_users(args: &Args) -> Result<Vec<User>, Box<dyn Error>> {
 }
 
 /* not sure if this is needed */
-pub fn log_all_tables(tables: &[Table]) -> () {
+pub fn log_all_tables(tables: &[Table]) {
     println!("length: {:?}", tables.len());
     for table in tables.iter() {
         debug!("Got table {}", table.table_name);
@@ -73,11 +74,26 @@ pub fn generate_email(
         .map(|(name, _)| format!("- {}", name))
         .collect();
     let table_list = table_names.join("\n");
-    let body = format!(
-        "Уважаемый(ая) {}!\n\n\
-        Следующие таблицы с расписаниями, которые вы отслеживаете, были изменены:\n{}\n\n\
-        Данное письмо было сгенерировано автоматически, направление ответа не подразумевается.",
-        user.name, table_list
+    let locale = user.locale;
+    let text = format!(
+        "{} {}!\n\n{}\n{}\n\n{}",
+        locale.tr("dear"),
+        user.name,
+        locale.tr("tables_changed"),
+        table_list,
+        locale.tr("auto_generated")
+    );
+    let table_items: String = watched_changes
+        .iter()
+        .map(|(name, _)| format!("<li>{}</li>", html::escape(name)))
+        .collect();
+    let html = format!(
+        "{} {}!<br><br>{}<ul>{}</ul>{}",
+        locale.tr("dear"),
+        html::escape(&user.name),
+        locale.tr("tables_changed"),
+        table_items,
+        locale.tr("auto_generated")
     );
     let email = Message::builder()
         .from(
@@ -88,9 +104,8 @@ pub fn generate_email(
             .parse()?,
         )
         .to(format!("{} <{}>", user.name, user.email).parse()?)
-        .subject("Обновление таблиц")
-        .header(ContentType::TEXT_PLAIN)
-        .body(body)?;
+        .subject(locale.tr("tables_subject"))
+        .multipart(MultiPart::alternative_plain_html(text, html))?;
 
     Ok(email)
 }
@@ -119,3 +134,7 @@ pub fn write_updated_table_hashes(
     );
     Ok(())
 }
+
+#[cfg(test)]
+#[path = "tests/tests.rs"]
+mod tests;
@@ -1,7 +1,9 @@
-use clap::{command, Parser};
+use clap::Parser;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use crate::{http_client::HttpSettings, locale::Locale};
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
@@ -18,6 +20,9 @@ pub struct User {
     pub name: String,
     pub watch_tables: Vec<String>,
     pub email: String,
+    /// Language of letters, Russian by default
+    #[serde(default)]
+    pub locale: Locale,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -26,6 +31,8 @@ pub struct Config {
     pub email_sender_username: String,
     pub email_sender_fullname: String,
     pub email_sender_password: String,
+    #[serde(flatten)]
+    pub http: HttpSettings,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
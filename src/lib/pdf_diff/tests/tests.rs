@@ -0,0 +1,58 @@
+use mailparse::{parse_mail, MailHeaderMap};
+
+use crate::http_client::HttpSettings;
+use crate::locale::Locale;
+
+use super::*;
+
+fn get_test_config() -> Config {
+    Config {
+        email_relay: "smtp.example.com".to_string(),
+        email_sender_username: "tables@example.com".to_string(),
+        email_sender_fullname: "Таблицы".to_string(),
+        email_sender_password: String::new(),
+        http: HttpSettings::default(),
+    }
+}
+
+#[test]
+fn changed_tables_are_sent_as_plain_text_and_html() {
+    let user = User {
+        name: "Энди Уорхол".to_string(),
+        watch_tables: vec!["Матмех & ПМ-ПУ <весна>".to_string(), "Физфак".to_string()],
+        email: "warhol@example.com".to_string(),
+        locale: Locale::Ru,
+    };
+    let changes = [
+        (
+            "Матмех & ПМ-ПУ <весна>".to_string(),
+            "https://example.com/1.pdf".to_string(),
+        ),
+        (
+            "Физфак".to_string(),
+            "https://example.com/2.pdf".to_string(),
+        ),
+    ];
+    let watched_changes = changes.iter().collect::<Vec<_>>();
+
+    let email = generate_email(&get_test_config(), &user, &watched_changes).unwrap();
+    let raw = email.formatted();
+    let parsed = parse_mail(&raw).unwrap();
+
+    assert_eq!(
+        parsed.headers.get_first_value("Subject").unwrap(),
+        "Обновление таблиц"
+    );
+    assert_eq!(parsed.ctype.mimetype, "multipart/alternative");
+    assert_eq!(parsed.subparts.len(), 2);
+    assert_eq!(parsed.subparts[0].ctype.mimetype, "text/plain");
+    assert_eq!(
+        parsed.subparts[0].get_body().unwrap(),
+        "Уважаемый(ая) Энди Уорхол!\r\n\r\nСледующие таблицы с расписаниями, которые вы отслеживаете, были изменены:\r\n- Матмех & ПМ-ПУ <весна>\r\n- Физфак\r\n\r\nДанное письмо было сгенерировано автоматически, направление ответа не подразумевается."
+    );
+    assert_eq!(parsed.subparts[1].ctype.mimetype, "text/html");
+    assert_eq!(
+        parsed.subparts[1].get_body().unwrap(),
+        "Уважаемый(ая) Энди Уорхол!<br><br>Следующие таблицы с расписаниями, которые вы отслеживаете, были изменены:<ul><li>Матмех &amp; ПМ-ПУ &lt;весна&gt;</li><li>Физфак</li></ul>Данное письмо было сгенерировано автоматически, направление ответа не подразумевается."
+    );
+}
@@ -0,0 +1,99 @@
+//! Holding back changes until they persist, so that half-edited schedules aren't reported
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+
+use super::models::{Config, PendingChange, Schedule, Timetable};
+
+/* whether a change persisted for long enough, changes are confirmed at once when no window is set */
+fn is_confirmed<T>(config: &Config, change: &PendingChange<T>, now: DateTime<Utc>) -> bool {
+    match (config.confirm_after_runs, config.confirm_after_minutes) {
+        (None, None) => true,
+        (runs, minutes) => {
+            runs.is_some_and(|runs| change.runs >= runs)
+                || minutes
+                    .and_then(|minutes| Duration::try_minutes(minutes.into()))
+                    .is_some_and(|window| now - change.first_seen >= window)
+        }
+    }
+}
+
+fn debounce_schedules<K: Ord + Clone, T: Timetable + Clone>(
+    config: &Config,
+    now: DateTime<Utc>,
+    old: &BTreeMap<K, T>,
+    old_pending: &BTreeMap<K, PendingChange<T>>,
+    new: &mut BTreeMap<K, T>,
+    new_pending: &mut BTreeMap<K, PendingChange<T>>,
+) {
+    for (key, new_events) in new.iter_mut() {
+        /* schedules which weren't fetched keep their pending changes as they were */
+        if new_pending.contains_key(key) {
+            continue;
+        }
+        let Some(old_events) = old.get(key) else {
+            continue;
+        };
+        /* a change which was rolled back is forgotten */
+        if old_events.days() == new_events.days() {
+            continue;
+        }
+        let change = match old_pending.get(key) {
+            Some(pending) if pending.events.days() == new_events.days() => PendingChange {
+                events: new_events.clone(),
+                first_seen: pending.first_seen,
+                runs: pending.runs + 1,
+            },
+            _ => PendingChange {
+                events: new_events.clone(),
+                first_seen: now,
+                runs: 1,
+            },
+        };
+        if !is_confirmed(config, &change, now) {
+            info!(
+                "Holding back changes of {} fetched {} times since {}",
+                new_events.display_name(),
+                change.runs,
+                change.first_seen
+            );
+            *new_events = old_events.clone();
+            new_pending.insert(key.clone(), change);
+        }
+    }
+}
+
+/// Replaces unconfirmed versions of schedules in `schedule_new` with their previous versions,
+/// moving them to `schedule_new.pending`. Should run after failed schedules are carried forward
+pub fn debounce_changes(
+    config: &Config,
+    now: DateTime<Utc>,
+    schedule_old: &Schedule,
+    schedule_new: &mut Schedule,
+) {
+    debounce_schedules(
+        config,
+        now,
+        &schedule_old.educators,
+        &schedule_old.pending.educators,
+        &mut schedule_new.educators,
+        &mut schedule_new.pending.educators,
+    );
+    debounce_schedules(
+        config,
+        now,
+        &schedule_old.groups,
+        &schedule_old.pending.groups,
+        &mut schedule_new.groups,
+        &mut schedule_new.pending.groups,
+    );
+    debounce_schedules(
+        config,
+        now,
+        &schedule_old.locations,
+        &schedule_old.pending.locations,
+        &mut schedule_new.locations,
+        &mut schedule_new.pending.locations,
+    );
+}
@@ -0,0 +1,175 @@
+//! Per-user filtering of schedule diffs
+use std::collections::BTreeMap;
+
+use chrono::{Days, NaiveDate};
+
+use super::models::{
+    day_of_week::DayOfWeek,
+    diff_model::{DayChange, DayDiff, EventsDiff, ModifiedEvent, ScheduleDiff},
+    educator_model::DayStudyEvent,
+    ChangedSchedules, Filters, ScheduleKey, User,
+};
+
+fn matches(text: &str, pattern: &str) -> bool {
+    text.to_lowercase().contains(&pattern.to_lowercase())
+}
+
+fn matches_any(text: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| matches(text, pattern))
+}
+
+/// Date of `weekday` of the week starting on `week`, if the week is known
+pub fn day_date(week: Option<NaiveDate>, weekday: DayOfWeek) -> Option<NaiveDate> {
+    week.map(|monday| monday + Days::new(weekday.num_days_from_monday()))
+}
+
+/// Dates an event takes place on: the day itself if its week is known, otherwise whatever the event lists
+pub fn event_dates(event: &DayStudyEvent, day: Option<NaiveDate>) -> Vec<NaiveDate> {
+    match day {
+        Some(day) => vec![day],
+        None => event
+            .dates
+            .iter()
+            .copied()
+            .chain(event.start.date())
+            .collect(),
+    }
+}
+
+struct EventFilter<'a> {
+    filters: &'a Filters,
+    /// First and last day changes of which are reported
+    period: Option<(NaiveDate, NaiveDate)>,
+    /// Date of the day being filtered, if its week is known
+    day: Option<NaiveDate>,
+}
+
+impl EventFilter<'_> {
+    fn keeps(&self, event: &DayStudyEvent) -> bool {
+        let filters = self.filters;
+        let groups_match = filters.groups.is_empty()
+            || event.contingent_unit_names.is_empty()
+            || event.contingent_unit_names.iter().any(|unit| {
+                matches_any(&format!("{} {}", unit.item1, unit.item2), &filters.groups)
+            });
+        let subject_matches = (filters.include_subjects.is_empty()
+            || matches_any(&event.subject, &filters.include_subjects))
+            && !matches_any(&event.subject, &filters.exclude_subjects);
+        /* events which can't be dated are kept */
+        let dates = event_dates(event, self.day);
+        let date_matches = match self.period {
+            Some((first, last)) => {
+                dates.is_empty() || dates.iter().any(|date| (first..=last).contains(date))
+            }
+            None => true,
+        };
+        groups_match && subject_matches && date_matches
+    }
+
+    fn events(&self, events: &[DayStudyEvent]) -> Vec<DayStudyEvent> {
+        events
+            .iter()
+            .filter(|event| self.keeps(event))
+            .cloned()
+            .collect()
+    }
+
+    /* a change is kept if the event was interesting before it or became interesting after */
+    fn modified_events(&self, events: &[ModifiedEvent]) -> Vec<ModifiedEvent> {
+        events
+            .iter()
+            .filter(|event| self.keeps(&event.old) || self.keeps(&event.new))
+            .cloned()
+            .collect()
+    }
+
+    fn day_change(&self, change: &DayChange) -> Option<DayChange> {
+        let change = match change {
+            DayChange::Added { events } => DayChange::Added {
+                events: self.events(events),
+            },
+            DayChange::Removed { events } => DayChange::Removed {
+                events: self.events(events),
+            },
+            DayChange::Modified(diff) => DayChange::Modified(EventsDiff {
+                cancelled: self.events(&diff.cancelled),
+                modified: self.modified_events(&diff.modified),
+                reassigned: diff
+                    .reassigned
+                    .iter()
+                    .filter(|reassignment| self.keeps(&reassignment.event))
+                    .cloned()
+                    .collect(),
+                added: self.events(&diff.added),
+                removed: self.events(&diff.removed),
+            }),
+        };
+        let is_empty = match &change {
+            DayChange::Added { events } | DayChange::Removed { events } => events.is_empty(),
+            DayChange::Modified(diff) => diff.is_empty(),
+        };
+        (!is_empty).then_some(change)
+    }
+}
+
+/// Part of `diff` which passes `filters`, `week` is Monday of the week the diff describes
+pub fn filter_schedule_diff(
+    filters: &Filters,
+    diff: &ScheduleDiff,
+    week: Option<NaiveDate>,
+    today: NaiveDate,
+) -> ScheduleDiff {
+    let period = filters.days_ahead.map(|days_ahead| {
+        let last = today
+            .checked_add_days(Days::new(days_ahead))
+            .unwrap_or(NaiveDate::MAX);
+        (today, last)
+    });
+    let days = diff
+        .days
+        .iter()
+        .filter_map(|(weekday, day)| {
+            let filter = EventFilter {
+                filters,
+                period,
+                day: day_date(week, *weekday),
+            };
+            let day = DayDiff {
+                day_string: day.day_string.clone(),
+                change: filter.day_change(&day.change)?,
+            };
+            Some((*weekday, day))
+        })
+        .collect();
+    ScheduleDiff {
+        days,
+        new_semester: diff.new_semester,
+    }
+}
+
+fn filter_diffs<'a, Id: Ord + Clone, T>(
+    diffs: &BTreeMap<ScheduleKey<Id>, (&'a T, ScheduleDiff)>,
+    filters: &Filters,
+    today: NaiveDate,
+) -> BTreeMap<ScheduleKey<Id>, (&'a T, ScheduleDiff)> {
+    diffs
+        .iter()
+        .filter_map(|(key, (events, diff))| {
+            let diff = filter_schedule_diff(filters, diff, key.1, today);
+            (!diff.is_empty()).then(|| (key.clone(), (*events, diff)))
+        })
+        .collect()
+}
+
+/// Changes the user wants to hear about, schedules left without changes are dropped
+pub fn filter_changes<'a>(
+    changed: &ChangedSchedules<'a>,
+    user: &User,
+    today: NaiveDate,
+) -> ChangedSchedules<'a> {
+    ChangedSchedules {
+        educators: filter_diffs(&changed.educators, &user.filters, today),
+        groups: filter_diffs(&changed.groups, &user.filters, today),
+        locations: filter_diffs(&changed.locations, &user.filters, today),
+    }
+}
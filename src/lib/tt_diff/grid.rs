@@ -0,0 +1,149 @@
+//! Week of a schedule as a table of weekdays and time slots, with changes marked in cells
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use super::models::{
+    date_time::TimeInterval,
+    day_of_week::{DayOfWeek, Days},
+    diff_model::{DayChange, EventsDiff, ModifiedEvent, ScheduleDiff},
+    educator_model::DayStudyEvent,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellStatus {
+    Kept,
+    /// New events, events of other educators and new versions of moved events
+    Added,
+    /// Events which stayed in their slot but changed otherwise
+    Changed,
+    /// Removed and cancelled events, old versions of moved events and events given to other educators
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GridEntry {
+    pub subject: String,
+    pub status: CellStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GridRow {
+    pub time: TimeInterval,
+    /// Entries of every day of `WeekGrid::days`, in the same order
+    pub cells: Vec<Vec<GridEntry>>,
+}
+
+/// New week of a schedule with removed events put back where they were
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeekGrid {
+    /// Weekdays with events in either version
+    pub days: Vec<DayOfWeek>,
+    pub rows: Vec<GridRow>,
+}
+
+fn moved(event: &ModifiedEvent) -> bool {
+    event.old.time_interval != event.new.time_interval
+}
+
+/* status of an event of the new version of a day */
+fn status_of(event: &DayStudyEvent, change: Option<&DayChange>) -> CellStatus {
+    let diff = match change {
+        None | Some(DayChange::Removed { .. }) => return CellStatus::Kept,
+        Some(DayChange::Added { .. }) => return CellStatus::Added,
+        Some(DayChange::Modified(diff)) => diff,
+    };
+    if diff.cancelled.contains(event) {
+        CellStatus::Removed
+    } else if diff.added.contains(event) || diff.reassigned.iter().any(|r| &r.event == event) {
+        CellStatus::Added
+    } else if let Some(modified) = diff.modified.iter().find(|m| &m.new == event) {
+        match moved(modified) {
+            true => CellStatus::Added,
+            false => CellStatus::Changed,
+        }
+    } else {
+        CellStatus::Kept
+    }
+}
+
+/* events of the old version of a day which the new one lacks in their slots. Reassignments are
+shared by both educators, so those missing from the new day went to someone else */
+fn removed_events<'a>(
+    change: &'a DayChange,
+    new_events: Option<&BTreeSet<DayStudyEvent>>,
+) -> Vec<&'a DayStudyEvent> {
+    match change {
+        DayChange::Added { .. } => Vec::new(),
+        DayChange::Removed { events } => events.iter().collect(),
+        DayChange::Modified(EventsDiff {
+            modified,
+            reassigned,
+            removed,
+            ..
+        }) => removed
+            .iter()
+            .chain(modified.iter().filter(|m| moved(m)).map(|m| &m.old))
+            .chain(
+                reassigned
+                    .iter()
+                    .map(|r| &r.event)
+                    .filter(|event| new_events.is_none_or(|events| !events.contains(event))),
+            )
+            .collect(),
+    }
+}
+
+impl WeekGrid {
+    /// Grid of the new `days` of a schedule and their `diff`, which holds old versions of changed events
+    pub fn new(days: &Days, diff: &ScheduleDiff) -> Self {
+        let mut entries: BTreeMap<DayOfWeek, Vec<(TimeInterval, GridEntry)>> = BTreeMap::new();
+        let mut add = |weekday, event: &DayStudyEvent, status| {
+            entries.entry(weekday).or_default().push((
+                event.time_interval.clone(),
+                GridEntry {
+                    subject: event.subject.clone(),
+                    status,
+                },
+            ))
+        };
+        for (weekday, day) in days {
+            let change = diff.days.get(weekday).map(|day| &day.change);
+            for event in &day.day_study_events {
+                add(*weekday, event, status_of(event, change));
+            }
+        }
+        for (weekday, day) in &diff.days {
+            let new_events = days.get(weekday).map(|day| &day.day_study_events);
+            for event in removed_events(&day.change, new_events) {
+                add(*weekday, event, CellStatus::Removed);
+            }
+        }
+
+        let slots: BTreeSet<TimeInterval> = entries
+            .values()
+            .flatten()
+            .map(|(time, _)| time.clone())
+            .collect();
+        let rows = slots
+            .into_iter()
+            .map(|time| GridRow {
+                cells: entries
+                    .values()
+                    .map(|day| {
+                        day.iter()
+                            .filter(|(slot, _)| *slot == time)
+                            .map(|(_, entry)| entry.clone())
+                            .collect()
+                    })
+                    .collect(),
+                time,
+            })
+            .collect();
+        WeekGrid {
+            days: entries.keys().copied().collect(),
+            rows,
+        }
+    }
+}
@@ -1,20 +1,57 @@
-use std::{collections::BTreeMap, error::Error, fs::File, io::BufReader};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::{self, Display},
+    fs::File,
+    future::Future,
+    io::BufReader,
+    time::Duration,
+};
 
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc};
 use itertools::Itertools;
-use lettre::{message::header::ContentType, Message};
-use log::{debug, info};
-use reqwest::Client;
+use lettre::{message::MultiPart, Message};
+use log::{debug, info, warn};
+use reqwest::{header::RETRY_AFTER, Client, Response, StatusCode};
+
+use crate::html;
 
-use super::models::{
-    educator_model::{DayStudyEvent, EducatorDay, EducatorEvents},
-    Args, Config, User,
+use super::{
+    filters::{day_date, event_dates},
+    models::{
+        diff_model::{DayChange, DayDiff, EventsDiff, ModifiedEvent, Reassignment, ScheduleDiff},
+        educator_model::{
+            DayStudyEvent, EducatorDay, EducatorEvents, EducatorSearchResults, FoundEducator,
+        },
+        group_model::GroupEvents,
+        location_model::LocationEvents,
+        Args, ChangedSchedules, Config, FetchFailure, PendingChange, PendingChanges,
+        PreviousEvents, Schedule, ScheduleKey, ScheduleRef, SchemaIssueKind, SchemaReport,
+        Subscriptions, Timetable, User,
+    },
+    rate_limiter::RateLimiter,
+    render::Letter,
+    schema_drift::SchemaChecker,
 };
 
-pub fn log_all_users(users: &[User]) -> () {
+/// Longest `Retry-After` we agree to wait, anything longer fails the request
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// Share of events which change at once when a schedule of a new semester is published
+const NEW_SEMESTER_SHARE: f64 = 0.8;
+
+/// Schedules with fewer events are never taken for a new semester
+const NEW_SEMESTER_MIN_EVENTS: usize = 5;
+
+pub fn log_all_users(users: &[User]) {
     for user in users.iter() {
         debug!(
-            "Serving {}, who is watching for educators {:?} and groups {:?}",
-            user.name, user.watch_educators, user.watch_groups
+            "Serving {}, who is watching for educators {:?} {:?}, groups {:?} and locations {:?}",
+            user.name,
+            user.watch_educators,
+            user.watch_educator_names,
+            user.watch_groups,
+            user.watch_locations
         );
     }
 }
@@ -30,185 +67,825 @@ pub fn get_users(args: &Args) -> Result<Vec<User>, Box<dyn Error>> {
     Ok(users)
 }
 
-pub fn get_previous_events(args: &Args) -> Result<BTreeMap<u32, EducatorEvents>, Box<dyn Error>> {
+fn by_key<T: Timetable>(schedules: Vec<T>) -> BTreeMap<ScheduleKey<T::Id>, T> {
+    schedules
+        .into_iter()
+        .map(|schedule| (schedule.key(), schedule))
+        .collect()
+}
+
+fn pending_by_key<T: Timetable>(
+    changes: Vec<PendingChange<T>>,
+) -> BTreeMap<ScheduleKey<T::Id>, PendingChange<T>> {
+    changes
+        .into_iter()
+        .map(|change| (change.events.key(), change))
+        .collect()
+}
+
+pub fn get_previous_events(args: &Args) -> Result<Schedule, Box<dyn Error>> {
     info!(
         "Reading previous events from {}",
         std::path::absolute(&args.previous_events_json_path)?.display()
     );
     if args.previous_events_json_path.exists() {
         let events_file = BufReader::new(File::open(&args.previous_events_json_path)?);
-        let events: Vec<EducatorEvents> = serde_json::from_reader(events_file)?;
-        let events_hm = events
-            .into_iter()
-            .map(|educator| (educator.educator_master_id.to_owned(), educator))
-            .collect::<BTreeMap<_, _>>();
-        Ok(events_hm)
+        let schedule = match serde_json::from_reader(events_file)? {
+            PreviousEvents::EducatorsOnly(educators) => Schedule {
+                educators: by_key(educators),
+                ..Default::default()
+            },
+            PreviousEvents::Full {
+                educators,
+                groups,
+                locations,
+                educator_names,
+                subscriptions,
+                pending_educators,
+                pending_groups,
+                pending_locations,
+            } => Schedule {
+                educators: by_key(educators),
+                groups: by_key(groups),
+                locations: by_key(locations),
+                educator_names,
+                subscriptions,
+                pending: PendingChanges {
+                    educators: pending_by_key(pending_educators),
+                    groups: pending_by_key(pending_groups),
+                    locations: pending_by_key(pending_locations),
+                },
+            },
+        };
+        Ok(schedule)
     } else {
-        Ok(BTreeMap::new())
+        Ok(Schedule::default())
+    }
+}
+
+/// Date of `now` in the timezone of the timetable
+pub fn local_date(config: &Config, now: DateTime<Utc>) -> NaiveDate {
+    now.with_timezone(&config.timezone).date_naive()
+}
+
+/// Monday of the week containing `date`
+pub fn week_monday(date: NaiveDate) -> NaiveDate {
+    date - Days::new(date.weekday().num_days_from_monday().into())
+}
+
+/// Mondays of the current week and `weeks_ahead` weeks after it
+pub fn watched_weeks(today: NaiveDate, weeks_ahead: u32) -> Vec<NaiveDate> {
+    let current_monday = week_monday(today);
+    (0..=weeks_ahead)
+        .map(|week| current_monday + Days::new(7 * u64::from(week)))
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum FetchError {
+    Http(reqwest::Error),
+    /// Server responded with 429 or 503, optionally telling when to come back
+    Throttled(StatusCode, Option<Duration>),
+    /// Response doesn't fit our model
+    Schema(String),
+}
+
+impl Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Http(error) => write!(f, "{}", error),
+            FetchError::Throttled(status, Some(retry_after)) => {
+                write!(
+                    f,
+                    "throttled with {}, retry after {:?}",
+                    status, retry_after
+                )
+            }
+            FetchError::Throttled(status, None) => write!(f, "throttled with {}", status),
+            FetchError::Schema(error) => write!(f, "unexpected response: {}", error),
+        }
+    }
+}
+
+impl Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(error: reqwest::Error) -> Self {
+        FetchError::Http(error)
+    }
+}
+
+/// Parses `Retry-After` header value, which is either a number of seconds or an HTTP date
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Sends GET request once rate limiter allows it. When the server throttles us,
+/// all following requests are paused for the time it asked for, unless it's too long
+/// to wait and the request is given up instead
+pub async fn send_request(
+    http_client: &Client,
+    rate_limiter: &RateLimiter,
+    url: &str,
+) -> Result<Response, FetchError> {
+    rate_limiter.wait().await;
+    let response = http_client.get(url).send().await?;
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_retry_after(value, Utc::now()));
+        if let Some(retry_after) = retry_after.filter(|delay| *delay <= MAX_RETRY_AFTER) {
+            rate_limiter.pause_for(retry_after).await;
+        }
+        return Err(FetchError::Throttled(status, retry_after));
     }
+    Ok(response.error_for_status()?)
 }
 
 pub async fn get_educator_events_by_id(
     http_client: &Client,
+    rate_limiter: &RateLimiter,
+    schema_checker: &SchemaChecker,
+    api_base_url: &str,
     id: u32,
-) -> Result<(u32, EducatorEvents), reqwest::Error> {
-    info!("Getting events for educator {}", id);
-    let request_url = format!("https://timetable.spbu.ru/api/v1/educators/{}/events", id);
-    let response = http_client.get(request_url).send().await?;
-    let educator: EducatorEvents = response.json().await?;
-    Ok((educator.educator_master_id.to_owned(), educator))
-}
-
-/* form string of information about changed event */
-fn format_event_as_string(event: &DayStudyEvent) -> String {
-    format!(
-        "    <b>Предмет:</b> {}<br>    <b>Время:</b> {}<br>    <b>Даты:</b> {}<br>    <b>Места:</b> {}<br>    <b>Направления:</b> {}<br>",
-        event.subject,
-        event.time_interval_string,
-        event.dates.iter().join(", "),
-        event
-            .event_locations
-            .iter()
-            .map(|loc| loc.display_name.clone())
-            .collect::<Vec<_>>()
-            .join(", "),
-        event
-            .contingent_unit_names
-            .iter()
-            .map(|c| format!("{} {}", c.item1, c.item2))
-            .collect::<Vec<_>>()
-            .join(", ")
-    )
+    week_start: NaiveDate,
+) -> Result<(ScheduleKey, EducatorEvents), FetchError> {
+    info!("Getting events for educator {} on week {}", id, week_start);
+    let request_url = format!(
+        "{}/educators/{}/events/{}/{}",
+        api_base_url.trim_end_matches('/'),
+        id,
+        week_start,
+        week_start + Days::new(7)
+    );
+    let response = send_request(http_client, rate_limiter, &request_url).await?;
+    let mut educator: EducatorEvents = schema_checker
+        .parse("educators/{id}/events", &response.bytes().await?)
+        .map_err(FetchError::Schema)?;
+    educator.week_start = Some(week_start);
+    Ok((educator.key(), educator))
+}
+
+pub async fn get_group_events_by_id(
+    http_client: &Client,
+    rate_limiter: &RateLimiter,
+    schema_checker: &SchemaChecker,
+    api_base_url: &str,
+    id: u32,
+    week_start: NaiveDate,
+) -> Result<(ScheduleKey, GroupEvents), FetchError> {
+    info!("Getting events for group {} on week {}", id, week_start);
+    let request_url = format!(
+        "{}/groups/{}/events/{}",
+        api_base_url.trim_end_matches('/'),
+        id,
+        week_start
+    );
+    let response = send_request(http_client, rate_limiter, &request_url).await?;
+    let group: GroupEvents = schema_checker
+        .parse("groups/{id}/events", &response.bytes().await?)
+        .map_err(FetchError::Schema)?;
+    Ok((group.key(), group))
+}
+
+pub async fn get_location_events_by_id(
+    http_client: &Client,
+    rate_limiter: &RateLimiter,
+    schema_checker: &SchemaChecker,
+    api_base_url: &str,
+    oid: &str,
+    week_start: NaiveDate,
+) -> Result<(ScheduleKey<String>, LocationEvents), FetchError> {
+    info!("Getting events for location {} on week {}", oid, week_start);
+    let request_url = format!(
+        "{}/classrooms/{}/events/{}/{}",
+        api_base_url.trim_end_matches('/'),
+        percent_encode_path_segment(oid),
+        week_start,
+        week_start + Days::new(7)
+    );
+    let response = send_request(http_client, rate_limiter, &request_url).await?;
+    let mut location: LocationEvents = schema_checker
+        .parse("classrooms/{oid}/events", &response.bytes().await?)
+        .map_err(FetchError::Schema)?;
+    location.oid = oid.to_string();
+    location.week_start = Some(week_start);
+    Ok((location.key(), location))
+}
+
+pub async fn search_educators(
+    http_client: &Client,
+    rate_limiter: &RateLimiter,
+    api_base_url: &str,
+    name: &str,
+) -> Result<Vec<FoundEducator>, FetchError> {
+    info!("Searching educators by name {}", name);
+    /* name goes into a single path segment, so it has to be percent-encoded */
+    let request_url = format!(
+        "{}/educators/search/{}",
+        api_base_url.trim_end_matches('/'),
+        percent_encode_path_segment(name.trim())
+    );
+    let response = send_request(http_client, rate_limiter, &request_url).await?;
+    let results: EducatorSearchResults = response.json().await?;
+    Ok(results.educators)
+}
+
+fn percent_encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
 }
 
-fn add_day_to_diff(cur_educator_diff: &mut Vec<String>, educator_day: &EducatorDay) {
-    if educator_day.day_study_events_count != 0 {
-        cur_educator_diff.push(format!("<em style=\"color:green;\">Новый день:</em>"));
-        cur_educator_diff.push(format!(
-            "<b><font size=\"5\">{}:</font></b><br>{}",
-            educator_day.day_string,
-            educator_day
-                .day_study_events
+/// Chooses the educator `name` refers to: either the only search result,
+/// or the only one whose full name matches exactly. Otherwise explains what went wrong
+pub fn choose_educator<'a>(
+    name: &str,
+    candidates: &'a [FoundEducator],
+) -> Result<&'a FoundEducator, String> {
+    let normalize = |name: &str| name.split_whitespace().join(" ").to_lowercase();
+    match candidates {
+        [] => Err(format!("no educators found by name \"{}\"", name)),
+        [only] => Ok(only),
+        _ => {
+            let mut exact = candidates
                 .iter()
-                .map(format_event_as_string)
-                .collect::<Vec<_>>()
-                .join("<br>")
-        ));
+                .filter(|candidate| normalize(&candidate.full_name) == normalize(name));
+            match (exact.next(), exact.next()) {
+                (Some(found), None) => Ok(found),
+                _ => Err(format!(
+                    "name \"{}\" is ambiguous, put one of these ids into watch_educators or specify full name: {}",
+                    name,
+                    candidates
+                        .iter()
+                        .map(|candidate| format!("{} ({})", candidate.id, candidate.full_name))
+                        .join(", ")
+                )),
+            }
+        }
     }
 }
 
-fn diff_educator_day(old_day: &EducatorDay, new_day: &EducatorDay) -> (Vec<String>, Vec<String>) {
-    let old_events = &old_day.day_study_events;
-    let new_events = &new_day.day_study_events;
+/// Repeats `request` with exponential backoff until it succeeds or retries run out.
+/// Client errors like 404 and unexpected responses are not retried, as repeating won't help.
+/// Throttled requests are repeated after the delay the server asked for
+pub async fn with_retries<T, F, Fut>(config: &Config, request: F) -> Result<T, FetchError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, FetchError>>,
+{
+    let mut delay = Duration::from_millis(config.fetch_retry_delay_ms);
+    let mut attempt = 0;
+    loop {
+        let error = match request().await {
+            Ok(result) => return Ok(result),
+            Err(error) => error,
+        };
+        let wait = match &error {
+            FetchError::Http(http_error)
+                if http_error.status().is_some_and(|s| s.is_client_error()) =>
+            {
+                return Err(error)
+            }
+            FetchError::Throttled(_, Some(retry_after)) if *retry_after > MAX_RETRY_AFTER => {
+                return Err(error)
+            }
+            FetchError::Schema(_) => return Err(error),
+            FetchError::Throttled(_, Some(retry_after)) => *retry_after,
+            _ => delay,
+        };
+        if attempt >= config.fetch_retries {
+            return Err(error);
+        }
+        attempt += 1;
+        warn!(
+            "Request failed: {}, retrying in {:?} ({}/{})",
+            error, wait, attempt, config.fetch_retries
+        );
+        tokio::time::sleep(wait).await;
+        delay *= 2;
+    }
+}
 
-    let added_events = new_events.difference(old_events);
-    let removed_events = old_events.difference(new_events);
+/// Keeps previous snapshots of schedules which couldn't be fetched together with their pending
+/// changes, so that they are neither reported as removed nor lost from the db
+pub fn carry_forward_failed(
+    schedule_old: &Schedule,
+    schedule_new: &mut Schedule,
+    failures: &[FetchFailure],
+) {
+    for failure in failures {
+        match &failure.schedule {
+            ScheduleRef::Educator(key) => {
+                if let Some(old) = schedule_old.educators.get(key) {
+                    schedule_new.educators.insert(*key, old.clone());
+                }
+                if let Some(pending) = schedule_old.pending.educators.get(key) {
+                    schedule_new.pending.educators.insert(*key, pending.clone());
+                }
+            }
+            ScheduleRef::Group(key) => {
+                if let Some(old) = schedule_old.groups.get(key) {
+                    schedule_new.groups.insert(*key, old.clone());
+                }
+                if let Some(pending) = schedule_old.pending.groups.get(key) {
+                    schedule_new.pending.groups.insert(*key, pending.clone());
+                }
+            }
+            ScheduleRef::Location(key) => {
+                if let Some(old) = schedule_old.locations.get(key) {
+                    schedule_new.locations.insert(key.clone(), old.clone());
+                }
+                if let Some(pending) = schedule_old.pending.locations.get(key) {
+                    schedule_new
+                        .pending
+                        .locations
+                        .insert(key.clone(), pending.clone());
+                }
+            }
+        }
+    }
+}
 
-    let mut removed_acc = Vec::new();
-    for event in removed_events {
-        removed_acc.push(format_event_as_string(event));
+/// Schedules each user watches, leaving out those which were never fetched
+pub fn current_subscriptions(
+    users: &[User],
+    schedule: &Schedule,
+) -> BTreeMap<String, Subscriptions> {
+    fn fetched<Id: Ord + Clone, T>(
+        watched: &BTreeSet<Id>,
+        schedules: &BTreeMap<ScheduleKey<Id>, T>,
+    ) -> BTreeSet<Id> {
+        schedules
+            .keys()
+            .map(|(id, _)| id)
+            .filter(|id| watched.contains(id))
+            .cloned()
+            .collect()
     }
-    if removed_acc.len() > 0 {
-        removed_acc.insert(
-            0,
-            "<em style=\"color:red;\">Удалённые события:</em>".to_string(),
-        )
+    users
+        .iter()
+        .map(|user| {
+            let subscriptions = Subscriptions {
+                educators: fetched(&user.watch_educators, &schedule.educators),
+                groups: fetched(&user.watch_groups, &schedule.groups),
+                locations: fetched(&user.watch_locations, &schedule.locations),
+            };
+            (user.email.clone(), subscriptions)
+        })
+        .collect()
+}
+
+/// Subscriptions from `current` missing in `previous`. Nothing is new when previous
+/// subscriptions are unknown, so that upgrading doesn't welcome everyone once again
+pub fn new_subscriptions(
+    previous: Option<&BTreeMap<String, Subscriptions>>,
+    current: &BTreeMap<String, Subscriptions>,
+) -> BTreeMap<String, Subscriptions> {
+    let Some(previous) = previous else {
+        return BTreeMap::new();
+    };
+    current
+        .iter()
+        .map(|(email, subscriptions)| {
+            let known = previous.get(email).cloned().unwrap_or_default();
+            (email.clone(), subscriptions.difference(&known))
+        })
+        .filter(|(_, subscriptions)| !subscriptions.is_empty())
+        .collect()
+}
+
+/// Stops reporting changes of `subscriptions` to the user for this run
+pub fn unwatch(user: &mut User, subscriptions: &Subscriptions) {
+    user.watch_educators
+        .retain(|id| !subscriptions.educators.contains(id));
+    user.watch_groups
+        .retain(|id| !subscriptions.groups.contains(id));
+    user.watch_locations
+        .retain(|oid| !subscriptions.locations.contains(oid));
+}
+
+pub fn log_fetch_failures(failures: &[FetchFailure]) {
+    if failures.is_empty() {
+        info!("All timetable requests succeeded");
+        return;
     }
-    let mut added_acc = Vec::new();
-    for event in added_events {
-        added_acc.push(format_event_as_string(event));
+    warn!(
+        "{} timetable requests failed, previous events were kept for them:",
+        failures.len()
+    );
+    for failure in failures {
+        warn!("    {}", failure);
     }
-    if added_acc.len() > 0 {
-        added_acc.insert(
-            0,
-            "<em style=\"color:green;\">Новые события:</em>".to_string(),
-        )
+}
+
+pub fn log_schema_report(report: &SchemaReport) {
+    for (endpoint, issues) in &report.endpoints {
+        warn!("Responses of {} don't match the model:", endpoint);
+        for issue in issues {
+            warn!("    {:?} {} {}", issue.kind, issue.path, issue.detail);
+        }
     }
-    return (removed_acc, added_acc);
 }
 
-fn add_tracked_educator_to_diff<'a>(
-    educator_old_events: &'a EducatorEvents,
-    educator_new_events: &'a EducatorEvents,
-) -> Vec<String> {
-    let mut cur_educator_diff = Vec::new();
+/* whether `new` is `old` which differs only in what its flags explain */
+fn is_flagged_change(old: &DayStudyEvent, new: &DayStudyEvent) -> bool {
+    (new.is_cancelled
+        || new.time_was_changed
+        || new.locations_were_changed
+        || new.educators_were_reassigned)
+        && old.subject == new.subject
+        && old.contingent_unit_names == new.contingent_unit_names
+        && (new.time_was_changed
+            || (&old.start, &old.end, &old.time_interval)
+                == (&new.start, &new.end, &new.time_interval))
+        && (new.locations_were_changed || old.event_locations == new.event_locations)
+        && (new.educators_were_reassigned
+            || old.educators_display_text == new.educators_display_text)
+}
 
-    for day in 0..6 {
-        let old_day = &educator_old_events.educator_events_days[day];
-        let new_day = &educator_new_events.educator_events_days[day];
+fn is_same_slot(old: &DayStudyEvent, new: &DayStudyEvent) -> bool {
+    old.subject == new.subject
+        && old.time_interval == new.time_interval
+        && old.contingent_unit_names == new.contingent_unit_names
+}
 
-        match old_day.day_study_events_count {
-            0 => {
-                if !(new_day.day_study_events_count == 0) {
-                    add_day_to_diff(&mut cur_educator_diff, new_day)
-                }
-            }
-            _ => {
-                let (removed, added) = diff_educator_day(old_day, new_day);
-                let mut combined = added.clone();
-                combined.extend(removed);
-                if combined.len() > 0 {
-                    cur_educator_diff.push(format!(
-                        "<b><font size=\"5\">{}:</font></b>",
-                        new_day.day_string,
-                    ));
-                    cur_educator_diff.extend(combined);
+fn is_moved_in_time(old: &DayStudyEvent, new: &DayStudyEvent) -> bool {
+    old.subject == new.subject
+        && old.contingent_unit_names == new.contingent_unit_names
+        && old.event_locations == new.event_locations
+}
+
+fn is_regrouped(old: &DayStudyEvent, new: &DayStudyEvent) -> bool {
+    old.subject == new.subject
+        && old.time_interval == new.time_interval
+        && old.event_locations == new.event_locations
+}
+
+/* ways to recognise an event after it was changed, from the most certain to the least */
+const SAME_EVENT_CHECKS: [fn(&DayStudyEvent, &DayStudyEvent) -> bool; 4] = [
+    is_flagged_change,
+    is_same_slot,
+    is_moved_in_time,
+    is_regrouped,
+];
+
+fn diff_day_events(old_day: &EducatorDay, new_day: &EducatorDay) -> EventsDiff {
+    let old_events = &old_day.day_study_events;
+    let new_events = &new_day.day_study_events;
+
+    /* every removed event is matched with at most one added event */
+    let mut removed_events = old_events.difference(new_events).collect::<Vec<_>>();
+    let mut added_events = new_events.difference(old_events).collect::<Vec<_>>();
+    let mut changed_events = Vec::new();
+    for is_same_event in SAME_EVENT_CHECKS {
+        added_events.retain(|&new_event| {
+            match removed_events
+                .iter()
+                .position(|old_event| is_same_event(old_event, new_event))
+            {
+                Some(index) => {
+                    changed_events.push((removed_events.remove(index), new_event));
+                    false
                 }
+                None => true,
             }
+        });
+    }
+    let (mut cancelled_events, added_events): (Vec<_>, Vec<_>) = added_events
+        .into_iter()
+        .partition(|event| event.is_cancelled);
+    let mut modified_events = Vec::new();
+    for (old_event, new_event) in changed_events {
+        if new_event.is_cancelled {
+            cancelled_events.push(new_event);
+        } else {
+            modified_events.push(ModifiedEvent::new(old_event.clone(), new_event.clone()));
         }
     }
-    cur_educator_diff
+
+    EventsDiff {
+        cancelled: cancelled_events.into_iter().cloned().collect(),
+        modified: modified_events,
+        reassigned: Vec::new(),
+        added: added_events.into_iter().cloned().collect(),
+        removed: removed_events.into_iter().cloned().collect(),
+    }
+}
+
+fn added_day(day: &EducatorDay) -> Option<DayDiff> {
+    (!day.day_study_events.is_empty()).then(|| DayDiff {
+        day_string: day.day_string.clone(),
+        change: DayChange::Added {
+            events: day.day_study_events.iter().cloned().collect(),
+        },
+    })
+}
+
+fn all_events<T: Timetable>(schedule: &T) -> impl Iterator<Item = &DayStudyEvent> {
+    schedule
+        .days()
+        .values()
+        .flat_map(|day| &day.day_study_events)
 }
 
-fn add_untracked_educator_to_diff<'a>(educator_events: &'a EducatorEvents) -> Vec<String> {
-    let mut cur_educator_diff = Vec::new();
+/* a new semester either moves all dates past the old ones or replaces most events at once.
+Small new schedules are always diffed, as a couple of changes is already most of them,
+and so are emptied ones, as there is no new semester in them */
+fn is_new_semester<T: Timetable>(old_events: &T, new_events: &T) -> bool {
+    let new_count = all_events(new_events).count();
+    if new_count < NEW_SEMESTER_MIN_EVENTS {
+        return false;
+    }
+    let old_last = all_events(old_events).flat_map(|event| &event.dates).max();
+    let new_first = all_events(new_events).flat_map(|event| &event.dates).min();
+    if let (Some(old_last), Some(new_first)) = (old_last, new_first) {
+        if new_first > old_last {
+            return true;
+        }
+    }
+    let kept: usize = old_events
+        .days()
+        .iter()
+        .filter_map(|(weekday, old_day)| {
+            let new_day = new_events.days().get(weekday)?;
+            Some(
+                old_day
+                    .day_study_events
+                    .intersection(&new_day.day_study_events)
+                    .count(),
+            )
+        })
+        .sum();
+    (new_count - kept) as f64 >= NEW_SEMESTER_SHARE * new_count as f64
+}
 
-    for new_day in &educator_events.educator_events_days {
-        add_day_to_diff(&mut cur_educator_diff, new_day);
+fn diff_tracked_schedule<T: Timetable>(old_events: &T, new_events: &T) -> ScheduleDiff {
+    if is_new_semester(old_events, new_events) {
+        info!(
+            "Schedule of {} changed for a new semester",
+            new_events.display_name()
+        );
+        return ScheduleDiff::new_semester();
     }
+    let mut diff = ScheduleDiff::default();
+    let old_days = old_events.days();
+    let new_days = new_events.days();
 
-    cur_educator_diff
+    /* days are matched by weekday, as group schedules list only days with events */
+    for (weekday, new_day) in new_days {
+        let day_diff = match old_days.get(weekday) {
+            Some(old_day) if !old_day.day_study_events.is_empty() => {
+                let events_diff = diff_day_events(old_day, new_day);
+                (!events_diff.is_empty()).then(|| DayDiff {
+                    day_string: new_day.day_string.clone(),
+                    change: DayChange::Modified(events_diff),
+                })
+            }
+            _ => added_day(new_day),
+        };
+        diff.days
+            .extend(day_diff.map(|day_diff| (*weekday, day_diff)));
+    }
+    for (weekday, old_day) in old_days {
+        if !new_days.contains_key(weekday) && !old_day.day_study_events.is_empty() {
+            let day_diff = DayDiff {
+                day_string: old_day.day_string.clone(),
+                change: DayChange::Removed {
+                    events: old_day.day_study_events.iter().cloned().collect(),
+                },
+            };
+            diff.days.insert(*weekday, day_diff);
+        }
+    }
+    diff
 }
 
-pub fn generate_diff_messages<'a>(
-    educators_old: &'a BTreeMap<u32, EducatorEvents>,
-    educators_new: &'a BTreeMap<u32, EducatorEvents>,
-) -> BTreeMap<u32, (&'a EducatorEvents, String)> {
-    let mut educators_new_w_messages = BTreeMap::new();
+pub fn generate_diffs<'a, T: Timetable>(
+    educators_old: &'a BTreeMap<ScheduleKey<T::Id>, T>,
+    educators_new: &'a BTreeMap<ScheduleKey<T::Id>, T>,
+) -> BTreeMap<ScheduleKey<T::Id>, (&'a T, ScheduleDiff)> {
+    let mut educators_new_w_diffs = BTreeMap::new();
 
-    /* for every found educator look for their old events,
-    if there are none, insert all their events into the diff */
-    for (&educator_id, new_events) in educators_new {
-        let educator_diff = match educators_old.get(&educator_id) {
-            Some(old_events) => add_tracked_educator_to_diff(old_events, new_events),
-            None => add_untracked_educator_to_diff(new_events),
+    /* for every found educator look for their old events of the same week,
+    events of new weeks and of educators which were never tracked become a baseline for later diffs */
+    for (key, new_events) in educators_new {
+        let educator_diff = match educators_old.get(key) {
+            Some(old_events) => diff_tracked_schedule(old_events, new_events),
+            None => {
+                debug!(
+                    "No previous events of {} for week {:?}, saving them without diff",
+                    new_events.display_name(),
+                    key.1
+                );
+                ScheduleDiff::default()
+            }
         };
-        if educator_diff.len() > 0 {
-            educators_new_w_messages.insert(educator_id, (new_events, educator_diff.join("<br>")));
+        if !educator_diff.is_empty() {
+            educators_new_w_diffs.insert(key.clone(), (new_events, educator_diff));
         }
     }
 
-    educators_new_w_messages
+    educators_new_w_diffs
 }
 
-pub fn collect_all_tracked_diffs(
-    educators_changed: &BTreeMap<u32, (&EducatorEvents, String)>,
-    user: &User,
-) -> String {
-    let mut acc: Vec<String> = Vec::new();
-    for educator in user.watch_educators.iter() {
-        if let Some((events, diff)) = educators_changed.get(educator) {
-            let cur_ed_diff = format!(
-                "В расписании преподавателя <b>{}</b> произошли изменения:<br><br>{}<br>",
-                events.educator_long_display_text, diff
-            );
-            acc.push(cur_ed_diff);
+/* the same class given by another educator, its room may differ */
+fn is_same_assignment(old: &DayStudyEvent, new: &DayStudyEvent) -> bool {
+    old.subject == new.subject
+        && old.time_interval == new.time_interval
+        && old.contingent_unit_names == new.contingent_unit_names
+        && old.dates == new.dates
+}
+
+/* events of a day from `removed` or `added` lists, wherever the day change keeps them */
+fn day_events(change: &DayChange, removed: bool) -> &[DayStudyEvent] {
+    match change {
+        DayChange::Added { events } if !removed => events,
+        DayChange::Removed { events } if removed => events,
+        DayChange::Modified(diff) if removed => &diff.removed,
+        DayChange::Modified(diff) => &diff.added,
+        _ => &[],
+    }
+}
+
+/// Turns events removed from one educator and added to another on the same day
+/// into reassignments, reported under both educators
+pub fn detect_reassignments(
+    educators: &mut BTreeMap<ScheduleKey, (&EducatorEvents, ScheduleDiff)>,
+) {
+    let mut reassignments = Vec::new();
+    let mut taken = Vec::new();
+    for ((from_key, (from, from_diff)), (to_key, (to, to_diff))) in
+        educators.iter().cartesian_product(educators.iter())
+    {
+        if from_key == to_key || from_key.1 != to_key.1 {
+            continue;
+        }
+        for (weekday, from_day) in &from_diff.days {
+            let Some(to_day) = to_diff.days.get(weekday) else {
+                continue;
+            };
+            for old_event in day_events(&from_day.change, true) {
+                if taken.contains(&(*from_key, *weekday, old_event)) {
+                    continue;
+                }
+                let Some(new_event) = day_events(&to_day.change, false).iter().find(|new_event| {
+                    is_same_assignment(old_event, new_event)
+                        && !taken.contains(&(*to_key, *weekday, *new_event))
+                }) else {
+                    continue;
+                };
+                taken.push((*from_key, *weekday, old_event));
+                taken.push((*to_key, *weekday, new_event));
+                let reassignment = Reassignment {
+                    event: new_event.clone(),
+                    from: from.display_name().to_string(),
+                    to: to.display_name().to_string(),
+                };
+                reassignments.push((
+                    *from_key,
+                    *to_key,
+                    *weekday,
+                    old_event.clone(),
+                    reassignment,
+                ));
+            }
         }
     }
-    return acc.join("<br> <br>");
+    for (from_key, to_key, weekday, old_event, reassignment) in reassignments {
+        if let Some((_, diff)) = educators.get_mut(&from_key) {
+            let day = diff.days.get_mut(&weekday).unwrap().change.as_modified();
+            day.removed.retain(|event| *event != old_event);
+            day.reassigned.push(reassignment.clone());
+        }
+        if let Some((_, diff)) = educators.get_mut(&to_key) {
+            let day = diff.days.get_mut(&weekday).unwrap().change.as_modified();
+            day.added.retain(|event| *event != reassignment.event);
+            day.reassigned.push(reassignment);
+        }
+    }
+}
+
+fn without_past_dates(event: &DayStudyEvent, today: NaiveDate) -> DayStudyEvent {
+    DayStudyEvent {
+        dates: event.dates.range(today..).copied().collect(),
+        ..event.clone()
+    }
 }
 
-pub fn generate_email(config: &Config, user: &User, diff: &str) -> Result<Message, Box<dyn Error>> {
+/* events which took place only before `today`, dated like filters date them.
+Events without any date can't be told apart, so they are kept */
+fn is_past(event: &DayStudyEvent, day: Option<NaiveDate>, today: NaiveDate) -> bool {
+    event_dates(event, day)
+        .into_iter()
+        .max()
+        .is_some_and(|last| last < today)
+}
+
+/* the API keeps rewriting dates of events which already happened, so only upcoming dates
+count as changes. Events which differ only in past dates aren't changed at all, and events
+which are over are neither new nor removed. Days of weekly schedules are dated by their week */
+fn drop_past_dates<Id: Ord, T>(
+    diffs: &mut BTreeMap<ScheduleKey<Id>, (&T, ScheduleDiff)>,
+    today: NaiveDate,
+) {
+    for ((_, week), (_, diff)) in diffs.iter_mut() {
+        diff.days.retain(|weekday, day| {
+            let date = day_date(*week, *weekday);
+            let is_past = |event: &DayStudyEvent| is_past(event, date, today);
+            match &mut day.change {
+                DayChange::Modified(events) => {
+                    events.modified.retain_mut(|event| {
+                        event.added_dates.retain(|date| *date >= today);
+                        event.removed_dates.retain(|date| *date >= today);
+                        !(is_past(&event.old) && is_past(&event.new))
+                            && without_past_dates(&event.old, today)
+                                != without_past_dates(&event.new, today)
+                    });
+                    events.cancelled.retain(|event| !is_past(event));
+                    events.added.retain(|event| !is_past(event));
+                    events.removed.retain(|event| !is_past(event));
+                    events
+                        .reassigned
+                        .retain(|reassignment| !is_past(&reassignment.event));
+                    !events.is_empty()
+                }
+                DayChange::Added { events } | DayChange::Removed { events } => {
+                    events.retain(|event| !is_past(event));
+                    !events.is_empty()
+                }
+            }
+        });
+    }
+    diffs.retain(|_, (_, diff)| !diff.is_empty());
+}
+
+/* a new semester is published for all weeks at once, so weekly notices of the same schedule
+are merged into one which isn't bound to a week */
+fn merge_new_semesters<Id: Ord + Clone, T>(
+    diffs: &mut BTreeMap<ScheduleKey<Id>, (&T, ScheduleDiff)>,
+) {
+    let weekly = diffs
+        .iter()
+        .filter(|((_, week), (_, diff))| week.is_some() && diff.new_semester)
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    for (id, week) in weekly {
+        if let Some((events, diff)) = diffs.remove(&(id.clone(), week)) {
+            diffs.entry((id, None)).or_insert((events, diff));
+        }
+    }
+}
+
+/// Diffs of all schedules, changes of dates before `today` are left out.
+/// A new semester is reported once per schedule rather than once per week
+pub fn generate_all_diffs<'a>(
+    schedule_old: &'a Schedule,
+    schedule_new: &'a Schedule,
+    today: NaiveDate,
+) -> ChangedSchedules<'a> {
+    let mut educators = generate_diffs(&schedule_old.educators, &schedule_new.educators);
+    detect_reassignments(&mut educators);
+    let mut groups = generate_diffs(&schedule_old.groups, &schedule_new.groups);
+    let mut locations = generate_diffs(&schedule_old.locations, &schedule_new.locations);
+    drop_past_dates(&mut educators, today);
+    drop_past_dates(&mut groups, today);
+    drop_past_dates(&mut locations, today);
+    merge_new_semesters(&mut educators);
+    merge_new_semesters(&mut groups);
+    merge_new_semesters(&mut locations);
+    ChangedSchedules {
+        educators,
+        groups,
+        locations,
+    }
+}
+
+/* a letter to a user with the subject under the given translation key */
+fn build_email(
+    config: &Config,
+    user: &User,
+    letter: &Letter,
+    subject_key: &str,
+) -> Result<Message, Box<dyn Error>> {
     let email = Message::builder()
         .from(
             format!(
@@ -218,36 +895,138 @@ pub fn generate_email(config: &Config, user: &User, diff: &str) -> Result<Messag
             .parse()?,
         )
         .to(format!("{} <{}>", user.name, user.email).parse()?)
-        .subject(format!(
-            "Изменилось расписание преподавателя!"
-        ))
-        .header(ContentType::TEXT_HTML)
-        .body(format!("Уважаемый(ая) {}!<br><br> {} <br> Данное письмо было сгенерировано автоматически, направление ответа не подразумевается.", user.name, diff))?;
+        .subject(user.locale.tr(subject_key))
+        .multipart(MultiPart::alternative_plain_html(
+            letter.text.clone(),
+            letter.html.clone(),
+        ))?;
 
     Ok(email)
 }
 
-pub fn write_previous_events(
-    args: &Args,
-    educator_events_new: BTreeMap<u32, EducatorEvents>,
-) -> Result<(), Box<dyn Error>> {
-    let is_test = &args
+pub fn generate_email(
+    config: &Config,
+    user: &User,
+    letter: &Letter,
+) -> Result<Message, Box<dyn Error>> {
+    build_email(config, user, letter, "changes_subject")
+}
+
+pub fn generate_welcome_email(
+    config: &Config,
+    user: &User,
+    letter: &Letter,
+) -> Result<Message, Box<dyn Error>> {
+    build_email(config, user, letter, "welcome_subject")
+}
+
+pub fn generate_schema_report_email(
+    config: &Config,
+    admin_email: &str,
+    report: &SchemaReport,
+) -> Result<Message, Box<dyn Error>> {
+    let mut lines = Vec::new();
+    for (endpoint, issues) in &report.endpoints {
+        lines.push((
+            format!("<b>{}</b>:", html::escape(endpoint)),
+            format!("{}:", endpoint),
+        ));
+        for issue in issues {
+            let kind = match issue.kind {
+                SchemaIssueKind::Unknown => "Неизвестное поле",
+                SchemaIssueKind::Missing => "Отсутствует поле",
+                SchemaIssueKind::Retyped => "Изменился формат поля",
+                SchemaIssueKind::Dropped => "Пропущен элемент",
+            };
+            let detail = if issue.detail.is_empty() {
+                String::new()
+            } else {
+                format!(": {}", issue.detail)
+            };
+            /* details come from error messages of responses */
+            let line = format!("    {} {}{}", kind, issue.path, detail);
+            lines.push((html::escape(&line), line));
+        }
+    }
+    let email = Message::builder()
+        .from(
+            format!(
+                "{} <{}>",
+                config.email_sender_fullname, config.email_sender_username
+            )
+            .parse()?,
+        )
+        .to(admin_email.parse()?)
+        .subject("Изменился формат ответов TimeTable")
+        .multipart(MultiPart::alternative_plain_html(
+            format!(
+                "Ответы TimeTable перестали совпадать с моделью:\n\n{}\n",
+                lines.iter().map(|(_, text)| text).join("\n")
+            ),
+            format!(
+                "Ответы TimeTable перестали совпадать с моделью:<br><br>{}<br>",
+                lines.iter().map(|(html, _)| html).join("<br>")
+            ),
+        ))?;
+
+    Ok(email)
+}
+
+/// Report written by the previous run, empty if there is none
+pub fn get_previous_schema_report(args: &Args) -> Result<SchemaReport, Box<dyn Error>> {
+    if !args.schema_report_json_path.exists() {
+        return Ok(SchemaReport::default());
+    }
+    let report_file = BufReader::new(File::open(&args.schema_report_json_path)?);
+
+    Ok(serde_json::from_reader(report_file)?)
+}
+
+pub fn write_schema_report(args: &Args, report: &SchemaReport) -> Result<(), Box<dyn Error>> {
+    let is_test = args
+        .schema_report_json_path
+        .to_str()
+        .unwrap()
+        .starts_with("tests/test.");
+    if is_test {
+        return Ok(());
+    }
+    info!(
+        "Writing schema report to {}",
+        std::path::absolute(&args.schema_report_json_path)?.display()
+    );
+    let report_file = File::create(&args.schema_report_json_path)?;
+
+    Ok(serde_json::to_writer_pretty(report_file, report)?)
+}
+
+pub fn write_previous_events(args: &Args, schedule_new: Schedule) -> Result<(), Box<dyn Error>> {
+    let is_test = args
         .previous_events_json_path
         .to_str()
         .unwrap()
         .starts_with("tests/test.");
-    if *is_test {
+    if is_test {
         return Ok(());
     }
     info!(
-        "Writing {} events to a {}",
-        educator_events_new.len(),
+        "Writing {} educators, {} groups and {} locations events to a {}",
+        schedule_new.educators.len(),
+        schedule_new.groups.len(),
+        schedule_new.locations.len(),
         std::path::absolute(&args.previous_events_json_path)?.display()
     );
 
-    let events = educator_events_new
-        .into_values()
-        .collect::<Vec<EducatorEvents>>();
+    let events = PreviousEvents::Full {
+        educators: schedule_new.educators.into_values().collect(),
+        groups: schedule_new.groups.into_values().collect(),
+        locations: schedule_new.locations.into_values().collect(),
+        educator_names: schedule_new.educator_names,
+        subscriptions: schedule_new.subscriptions,
+        pending_educators: schedule_new.pending.educators.into_values().collect(),
+        pending_groups: schedule_new.pending.groups.into_values().collect(),
+        pending_locations: schedule_new.pending.locations.into_values().collect(),
+    };
 
     let events_file = File::create(&args.previous_events_json_path)?;
 
@@ -1,38 +1,63 @@
-use std::collections::BTreeMap;
-
 use lettre::{SmtpTransport, Transport};
 use log::info;
 
 use crate::tt_diff::helpers::generate_email;
 
 use super::{
-    helpers::collect_all_tracked_diffs,
-    models::{educator_model::EducatorEvents, Config, User},
+    helpers::{generate_schema_report_email, generate_welcome_email},
+    models::{ChangedSchedules, Config, SchemaReport, User},
+    render::{Letter, Letters},
 };
 
 pub trait LetterSender {
+    /// Sends every user a letter with changes of their schedules, which are already filtered for them
     fn form_and_send_letters(
         self,
-        users: Vec<User>,
         config: Config,
-        ed_changed: BTreeMap<u32, (&EducatorEvents, String)>,
+        letters: &Letters,
+        changes: Vec<(User, ChangedSchedules)>,
     );
+
+    /// Sends the report to `admin_email` from config, if there is one
+    fn send_schema_report(&self, config: &Config, report: &SchemaReport);
+
+    /// Sends current state of newly watched schedules to their subscriber
+    fn send_welcome_letter(&self, config: &Config, user: &User, letter: &Letter);
 }
 
 impl LetterSender for SmtpTransport {
     fn form_and_send_letters(
         self,
-        users: Vec<User>,
         config: Config,
-        ed_changed: BTreeMap<u32, (&EducatorEvents, String)>,
+        letters: &Letters,
+        changes: Vec<(User, ChangedSchedules)>,
     ) {
-        for user in users.iter() {
-            let diff = collect_all_tracked_diffs(&ed_changed, user);
-            if diff.len() > 0 {
-                let email = generate_email(&config, user, &diff).unwrap();
+        for (user, changed) in changes.iter() {
+            if let Some(letter) = letters.changes_letter(changed, user).unwrap() {
+                let email = generate_email(&config, user, &letter).unwrap();
                 let code = self.send(&email).unwrap();
                 info!("Sent email to {} with response {:?}", user.name, code);
             }
         }
     }
+
+    fn send_welcome_letter(&self, config: &Config, user: &User, letter: &Letter) {
+        let email = generate_welcome_email(config, user, letter).unwrap();
+        let code = self.send(&email).unwrap();
+        info!(
+            "Sent welcome email to {} with response {:?}",
+            user.name, code
+        );
+    }
+
+    fn send_schema_report(&self, config: &Config, report: &SchemaReport) {
+        if let Some(admin_email) = &config.admin_email {
+            let email = generate_schema_report_email(config, admin_email, report).unwrap();
+            let code = self.send(&email).unwrap();
+            info!(
+                "Sent schema report to {} with response {:?}",
+                admin_email, code
+            );
+        }
+    }
 }
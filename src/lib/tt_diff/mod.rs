@@ -1,5 +1,11 @@
+pub mod debounce;
+pub mod filters;
+pub mod grid;
 pub mod helpers;
 pub mod letter_sender;
 pub mod models;
+pub mod rate_limiter;
+pub mod render;
 pub mod run_tool;
 pub mod schedule_getter;
+pub mod schema_drift;
@@ -1,17 +1,67 @@
-use std::{collections::BTreeSet, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
 
-use clap::{command, Parser};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use clap::Parser;
 use serde::{Deserialize, Serialize};
 
+use crate::{http_client::HttpSettings, locale::Locale};
+use day_of_week::Days;
+use diff_model::ScheduleDiff;
+use educator_model::EducatorEvents;
+use group_model::GroupEvents;
+use location_model::LocationEvents;
+
+pub mod date_time;
+pub mod day_of_week;
+pub mod diff_model;
 pub mod educator_model;
+pub mod group_model;
+pub mod location_model;
 
 /// Model for `users.json`
 #[derive(Debug, Deserialize, Serialize)]
 pub struct User {
     pub name: String,
     pub watch_educators: BTreeSet<u32>,
+    /// Educators to look up by name, resolved ids are cached in `previous_events.json`
+    #[serde(default)]
+    pub watch_educator_names: BTreeSet<String>,
     pub watch_groups: BTreeSet<u32>,
+    /// Oids of watched classrooms
+    #[serde(default)]
+    pub watch_locations: BTreeSet<String>,
     pub email: String,
+    #[serde(default)]
+    pub filters: Filters,
+    /// Language of letters, Russian by default
+    #[serde(default)]
+    pub locale: Locale,
+    /// Changes are also shown as a table of the new week, HTML letters only
+    #[serde(default)]
+    pub weekly_grid: bool,
+}
+
+/// Which changes of watched schedules a user wants to hear about.
+/// Patterns match any part of a name, case is ignored
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Filters {
+    /// Events of other groups are left out, events without groups are kept
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Events whose subject matches none of these are left out, unless the list is empty
+    #[serde(default)]
+    pub include_subjects: Vec<String>,
+    /// Events whose subject matches any of these are left out
+    #[serde(default)]
+    pub exclude_subjects: Vec<String>,
+    /// Changes later than this many days after today are left out, as well as past ones
+    #[serde(default)]
+    pub days_ahead: Option<u64>,
 }
 
 #[derive(Parser, Debug)]
@@ -23,6 +73,18 @@ pub struct Args {
     pub config_json_path: PathBuf,
     #[arg(long, value_name = "FILE", default_value = "previous_events.json")]
     pub previous_events_json_path: PathBuf,
+    #[arg(long, value_name = "FILE", default_value = "schema_report.json")]
+    pub schema_report_json_path: PathBuf,
+}
+
+/// Looks up educators ids by name
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct FindEducatorArgs {
+    #[arg(long, value_name = "FILE", default_value = "config.json")]
+    pub config_json_path: PathBuf,
+    /// Last name or its beginning, optionally followed by first names
+    pub name: String,
 }
 
 #[derive(Deserialize)]
@@ -31,4 +93,312 @@ pub struct Config {
     pub email_sender_username: String,
     pub email_sender_fullname: String,
     pub email_sender_password: String,
+    /// Root of timetable REST API, can point to a mock server or a caching mirror
+    #[serde(default = "default_api_base_url")]
+    pub api_base_url: String,
+    #[serde(flatten)]
+    pub http: HttpSettings,
+    /// How many weeks after the current one are fetched and diffed
+    #[serde(default)]
+    pub weeks_ahead: u32,
+    /// How many times a failed timetable request is repeated
+    #[serde(default = "default_fetch_retries")]
+    pub fetch_retries: u32,
+    /// Delay before the first retry, doubled after every failed attempt
+    #[serde(default = "default_fetch_retry_delay_ms")]
+    pub fetch_retry_delay_ms: u64,
+    /// How many timetable requests may be in flight at once
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// How many timetable requests may be started per second, non-positive disables the limit
+    #[serde(default = "default_requests_per_second")]
+    pub requests_per_second: f64,
+    /// Fill in missing and retyped fields of API responses instead of failing the request
+    #[serde(default)]
+    pub lenient_parsing: bool,
+    /// Where to send reports about changes in API responses
+    #[serde(default)]
+    pub admin_email: Option<String>,
+    /// What users get when they start watching a schedule
+    #[serde(default)]
+    pub new_subscriptions: NewSubscriptions,
+    /// Changes are reported once this many runs in a row fetched them
+    #[serde(default)]
+    pub confirm_after_runs: Option<u32>,
+    /// Changes are reported once this many minutes passed since they were first fetched
+    #[serde(default)]
+    pub confirm_after_minutes: Option<u32>,
+    /// Timezone of the timetable, changes of dates before today in it aren't reported
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+    #[serde(default)]
+    pub templates: TemplatePaths,
+}
+
+/// Files replacing built-in templates of letters, see `src/lib/tt_diff/templates`
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct TemplatePaths {
+    /// Macros rendering schedules and their changes, imported by letters
+    pub macros: Option<PathBuf>,
+    /// Letter about changes of watched schedules
+    pub changes: Option<PathBuf>,
+    /// Letter for new subscribers
+    pub welcome: Option<PathBuf>,
+    /// Plain-text counterparts of the templates above
+    pub macros_text: Option<PathBuf>,
+    pub changes_text: Option<PathBuf>,
+    pub welcome_text: Option<PathBuf>,
+}
+
+impl TemplatePaths {
+    /// File replacing the built-in template `name`, like "changes.html"
+    pub fn get(&self, name: &str) -> Option<&Path> {
+        match name {
+            "macros.html" => self.macros.as_deref(),
+            "changes.html" => self.changes.as_deref(),
+            "welcome.html" => self.welcome.as_deref(),
+            "macros.txt" => self.macros_text.as_deref(),
+            "changes.txt" => self.changes_text.as_deref(),
+            "welcome.txt" => self.welcome_text.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NewSubscriptions {
+    /// Nothing, the schedule is saved to report its later changes
+    #[default]
+    Silent,
+    /// Letter with the current schedule, sent only to the new subscriber
+    Welcome,
+}
+
+fn default_api_base_url() -> String {
+    "https://timetable.spbu.ru/api/v1".to_string()
+}
+
+fn default_fetch_retries() -> u32 {
+    2
+}
+
+fn default_fetch_retry_delay_ms() -> u64 {
+    1000
+}
+
+fn default_max_concurrent_requests() -> usize {
+    4
+}
+
+fn default_requests_per_second() -> f64 {
+    5.0
+}
+
+fn default_timezone() -> Tz {
+    Tz::Europe__Moscow
+}
+
+/// Id of a schedule and Monday of the week it describes.
+/// Snapshots written before weeks were tracked have no week
+pub type ScheduleKey<Id = u32> = (Id, Option<NaiveDate>);
+
+/// Anything with days of a week which can be diffed
+pub trait Timetable {
+    /// Educators and groups have numeric ids, classrooms have string oids
+    type Id: Ord + Clone;
+
+    fn key(&self) -> ScheduleKey<Self::Id>;
+    fn display_name(&self) -> &str;
+    fn days(&self) -> &Days;
+}
+
+/// State of all watched schedules, either fetched from timetable or read from `previous_events.json`
+#[derive(Debug, Default, PartialEq)]
+pub struct Schedule {
+    pub educators: BTreeMap<ScheduleKey, EducatorEvents>,
+    pub groups: BTreeMap<ScheduleKey, GroupEvents>,
+    pub locations: BTreeMap<ScheduleKey<String>, LocationEvents>,
+    /// Ids of educators from `watch_educator_names`
+    pub educator_names: BTreeMap<String, u32>,
+    /// Schedules users watched by the time of the snapshot, keyed by email.
+    /// Snapshots written before subscriptions were tracked have none
+    pub subscriptions: Option<BTreeMap<String, Subscriptions>>,
+    /// Versions of schedules which differ from the ones above, but aren't confirmed yet
+    pub pending: PendingChanges,
+}
+
+/// Version of a schedule which isn't reported until it persists for long enough
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PendingChange<T> {
+    pub events: T,
+    /// When this version was first fetched
+    pub first_seen: DateTime<Utc>,
+    /// How many runs in a row fetched this version
+    pub runs: u32,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PendingChanges {
+    pub educators: BTreeMap<ScheduleKey, PendingChange<EducatorEvents>>,
+    pub groups: BTreeMap<ScheduleKey, PendingChange<GroupEvents>>,
+    pub locations: BTreeMap<ScheduleKey<String>, PendingChange<LocationEvents>>,
+}
+
+impl PendingChanges {
+    pub fn len(&self) -> usize {
+        self.educators.len() + self.groups.len() + self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Watched schedules which were fetched at least once
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Subscriptions {
+    #[serde(default)]
+    pub educators: BTreeSet<u32>,
+    #[serde(default)]
+    pub groups: BTreeSet<u32>,
+    #[serde(default)]
+    pub locations: BTreeSet<String>,
+}
+
+impl Subscriptions {
+    pub fn is_empty(&self) -> bool {
+        self.educators.is_empty() && self.groups.is_empty() && self.locations.is_empty()
+    }
+
+    /// Subscriptions missing from `other`
+    pub fn difference(&self, other: &Subscriptions) -> Subscriptions {
+        Subscriptions {
+            educators: &self.educators - &other.educators,
+            groups: &self.groups - &other.groups,
+            locations: &self.locations - &other.locations,
+        }
+    }
+}
+
+/// Which schedule a request was made for
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleRef {
+    Educator(ScheduleKey),
+    Group(ScheduleKey),
+    Location(ScheduleKey<String>),
+}
+
+/// Timetable request which failed even after all retries
+#[derive(Debug, PartialEq)]
+pub struct FetchFailure {
+    pub schedule: ScheduleRef,
+    pub error: String,
+}
+
+impl Display for FetchFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, id, week) = match &self.schedule {
+            ScheduleRef::Educator((id, week)) => ("educator", id.to_string(), week),
+            ScheduleRef::Group((id, week)) => ("group", id.to_string(), week),
+            ScheduleRef::Location((oid, week)) => ("location", oid.clone(), week),
+        };
+        match week {
+            Some(week) => write!(f, "{} {} on week {}: {}", kind, id, week, self.error),
+            None => write!(f, "{} {}: {}", kind, id, self.error),
+        }
+    }
+}
+
+/// Timetable requests which failed, and differences between responses and our models
+#[derive(Debug, Default, PartialEq)]
+pub struct FetchReport {
+    pub failures: Vec<FetchFailure>,
+    pub schema_drift: SchemaReport,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaIssueKind {
+    /// Field we don't know about
+    Unknown,
+    /// Field our model requires, but the response lacks
+    Missing,
+    /// Field of unexpected type or format
+    Retyped,
+    /// List element which couldn't be repaired and was left out
+    Dropped,
+}
+
+/// Difference between an API response and our model, `path` has list indices erased
+/// and `detail` has values of the response left out
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct SchemaIssue {
+    pub kind: SchemaIssueKind,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+}
+
+/// Model for schema drift report, issues are grouped by endpoint
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct SchemaReport {
+    pub endpoints: BTreeMap<String, BTreeSet<SchemaIssue>>,
+}
+
+impl SchemaReport {
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    /// Issues which `previous` report lacks, so that the same drift isn't reported on every run
+    pub fn new_since(&self, previous: &SchemaReport) -> SchemaReport {
+        let endpoints = self
+            .endpoints
+            .iter()
+            .filter_map(|(endpoint, issues)| {
+                let known = previous.endpoints.get(endpoint);
+                let new = issues
+                    .iter()
+                    .filter(|issue| known.is_none_or(|known| !known.contains(issue)))
+                    .cloned()
+                    .collect::<BTreeSet<_>>();
+                (!new.is_empty()).then(|| (endpoint.clone(), new))
+            })
+            .collect();
+        SchemaReport { endpoints }
+    }
+}
+
+/// Model for `previous_events.json`
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum PreviousEvents {
+    /// Format used before groups were tracked: a bare list of educators
+    EducatorsOnly(Vec<EducatorEvents>),
+    Full {
+        educators: Vec<EducatorEvents>,
+        #[serde(default)]
+        groups: Vec<GroupEvents>,
+        #[serde(default)]
+        locations: Vec<LocationEvents>,
+        #[serde(default)]
+        educator_names: BTreeMap<String, u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        subscriptions: Option<BTreeMap<String, Subscriptions>>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pending_educators: Vec<PendingChange<EducatorEvents>>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pending_groups: Vec<PendingChange<GroupEvents>>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pending_locations: Vec<PendingChange<LocationEvents>>,
+    },
+}
+
+/// Diffs of every changed schedule together with its new state
+#[derive(Debug, Default)]
+pub struct ChangedSchedules<'a> {
+    pub educators: BTreeMap<ScheduleKey, (&'a EducatorEvents, ScheduleDiff)>,
+    pub groups: BTreeMap<ScheduleKey, (&'a GroupEvents, ScheduleDiff)>,
+    pub locations: BTreeMap<ScheduleKey<String>, (&'a LocationEvents, ScheduleDiff)>,
 }
@@ -0,0 +1,229 @@
+//! Typed dates and times of timetable events, serialized in timetable.spbu.ru's wire format
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+const TIME_FORMAT: &str = "%H:%M";
+const TIME_WITH_SECONDS_FORMAT: &str = "%H:%M:%S";
+const DATE_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+const DATE_FORMAT: &str = "%d.%m.%Y";
+/// Separator used by the API, plain hyphen is accepted too
+const INTERVAL_SEPARATOR: char = '–';
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value, TIME_FORMAT)
+        .or_else(|_| NaiveTime::parse_from_str(value, TIME_WITH_SECONDS_FORMAT))
+        .ok()
+}
+
+fn format_time(time: &NaiveTime) -> String {
+    time.format(TIME_FORMAT).to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Moment {
+    Time(NaiveTime),
+    DateTime(NaiveDateTime),
+}
+
+/// Start or end of an event. Educator schedules give only time, group schedules give full date.
+/// Compared by the moment alone, while the text the API sent is written back as is
+#[derive(Debug, Clone)]
+pub struct EventTime {
+    moment: Moment,
+    wire: String,
+}
+
+impl EventTime {
+    pub fn time(&self) -> NaiveTime {
+        match self.moment {
+            Moment::Time(time) => time,
+            Moment::DateTime(date_time) => date_time.time(),
+        }
+    }
+
+    pub fn date(&self) -> Option<NaiveDate> {
+        match self.moment {
+            Moment::Time(_) => None,
+            Moment::DateTime(date_time) => Some(date_time.date()),
+        }
+    }
+}
+
+impl PartialEq for EventTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.moment == other.moment
+    }
+}
+
+impl Eq for EventTime {}
+
+impl PartialOrd for EventTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EventTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.moment.cmp(&other.moment)
+    }
+}
+
+impl FromStr for EventTime {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let trimmed = value.trim();
+        let moment = match parse_time(trimmed) {
+            Some(time) => Moment::Time(time),
+            None => NaiveDateTime::parse_from_str(trimmed, DATE_TIME_FORMAT)
+                .map(Moment::DateTime)
+                .map_err(|_| {
+                    format!(
+                        "invalid event time \"{}\", expected HH:MM, HH:MM:SS or YYYY-MM-DDTHH:MM:SS",
+                        trimmed
+                    )
+                })?,
+        };
+        Ok(EventTime {
+            moment,
+            wire: value.to_string(),
+        })
+    }
+}
+
+impl Display for EventTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.moment {
+            Moment::Time(time) => write!(f, "{}", format_time(&time)),
+            Moment::DateTime(date_time) => write!(f, "{}", date_time.format(DATE_TIME_FORMAT)),
+        }
+    }
+}
+
+impl Serialize for EventTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.wire)
+    }
+}
+
+impl<'de> Deserialize<'de> for EventTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Time interval like "09:30–11:05". Compared by its start and end alone,
+/// while the text the API sent is written back as is
+#[derive(Debug, Clone)]
+pub struct TimeInterval {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    wire: String,
+}
+
+impl PartialEq for TimeInterval {
+    fn eq(&self, other: &Self) -> bool {
+        (self.start, self.end) == (other.start, other.end)
+    }
+}
+
+impl Eq for TimeInterval {}
+
+impl PartialOrd for TimeInterval {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimeInterval {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.start, self.end).cmp(&(other.start, other.end))
+    }
+}
+
+impl FromStr for TimeInterval {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            format!(
+                "invalid time interval \"{}\", expected HH:MM{}HH:MM",
+                value, INTERVAL_SEPARATOR
+            )
+        };
+        let (start, end) = value
+            .split_once([INTERVAL_SEPARATOR, '-'])
+            .ok_or_else(error)?;
+        Ok(TimeInterval {
+            start: parse_time(start.trim()).ok_or_else(error)?,
+            end: parse_time(end.trim()).ok_or_else(error)?,
+            wire: value.to_string(),
+        })
+    }
+}
+
+impl Display for TimeInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            format_time(&self.start),
+            INTERVAL_SEPARATOR,
+            format_time(&self.end)
+        )
+    }
+}
+
+impl Serialize for TimeInterval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.wire)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeInterval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+pub fn format_date(date: &NaiveDate) -> String {
+    date.format(DATE_FORMAT).to_string()
+}
+
+/// Serde adapter for lists of dates like "01.09.2024", their order and repeats are dropped
+pub mod dates {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        dates: &BTreeSet<NaiveDate>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(dates.iter().map(format_date))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeSet<NaiveDate>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|date| {
+                NaiveDate::parse_from_str(date.trim(), DATE_FORMAT).map_err(|_| {
+                    de::Error::custom(format!("invalid date \"{}\", expected DD.MM.YYYY", date))
+                })
+            })
+            .collect()
+    }
+}
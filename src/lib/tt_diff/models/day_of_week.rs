@@ -0,0 +1,121 @@
+//! Days of schedules keyed by weekday
+use std::{collections::BTreeMap, fmt};
+
+use chrono::{Datelike, Weekday};
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+
+use super::educator_model::EducatorDay;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum DayOfWeek {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl From<Weekday> for DayOfWeek {
+    fn from(weekday: Weekday) -> Self {
+        match weekday {
+            Weekday::Mon => DayOfWeek::Monday,
+            Weekday::Tue => DayOfWeek::Tuesday,
+            Weekday::Wed => DayOfWeek::Wednesday,
+            Weekday::Thu => DayOfWeek::Thursday,
+            Weekday::Fri => DayOfWeek::Friday,
+            Weekday::Sat => DayOfWeek::Saturday,
+            Weekday::Sun => DayOfWeek::Sunday,
+        }
+    }
+}
+
+impl DayOfWeek {
+    /// Weekday from names like "Понедельник" or "понедельник, 14 октября"
+    pub fn from_day_string(day_string: &str) -> Option<Self> {
+        let name = day_string.split(',').next()?.trim().to_lowercase();
+        match name.as_str() {
+            "понедельник" | "monday" => Some(DayOfWeek::Monday),
+            "вторник" | "tuesday" => Some(DayOfWeek::Tuesday),
+            "среда" | "wednesday" => Some(DayOfWeek::Wednesday),
+            "четверг" | "thursday" => Some(DayOfWeek::Thursday),
+            "пятница" | "friday" => Some(DayOfWeek::Friday),
+            "суббота" | "saturday" => Some(DayOfWeek::Saturday),
+            "воскресенье" | "sunday" => Some(DayOfWeek::Sunday),
+            _ => None,
+        }
+    }
+
+    pub fn num_days_from_monday(self) -> u64 {
+        self as u64
+    }
+
+    fn from_index(index: usize) -> Option<Self> {
+        const ALL: [DayOfWeek; 7] = [
+            DayOfWeek::Monday,
+            DayOfWeek::Tuesday,
+            DayOfWeek::Wednesday,
+            DayOfWeek::Thursday,
+            DayOfWeek::Friday,
+            DayOfWeek::Saturday,
+            DayOfWeek::Sunday,
+        ];
+        ALL.get(index).copied()
+    }
+}
+
+pub type Days = BTreeMap<DayOfWeek, EducatorDay>;
+
+/* date of the day is the most reliable, then its name, then position in a list starting on Monday */
+fn weekday_of(day: &EducatorDay, index: usize) -> Option<DayOfWeek> {
+    day.day
+        .map(|date| date.weekday().into())
+        .or_else(|| DayOfWeek::from_day_string(&day.day_string))
+        .or_else(|| DayOfWeek::from_index(index))
+}
+
+struct DaysVisitor;
+
+impl<'de> Visitor<'de> for DaysVisitor {
+    type Value = Days;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of days or a map from weekday to day")
+    }
+
+    /* API responses and files written before days were keyed by weekday */
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut days = Days::new();
+        let mut index = 0;
+        while let Some(day) = seq.next_element::<EducatorDay>()? {
+            let weekday = weekday_of(&day, index).ok_or_else(|| {
+                de::Error::custom(format!("can't tell weekday of day \"{}\"", day.day_string))
+            })?;
+            match days.get_mut(&weekday) {
+                Some(same_weekday) => same_weekday.day_study_events.extend(day.day_study_events),
+                None => {
+                    days.insert(weekday, day);
+                }
+            }
+            index += 1;
+        }
+        Ok(days)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut days = Days::new();
+        while let Some((weekday, day)) = map.next_entry()? {
+            days.insert(weekday, day);
+        }
+        Ok(days)
+    }
+}
+
+/// Serde adapter accepting days both as a list and as a map
+pub fn deserialize_days<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Days, D::Error> {
+    deserializer.deserialize_any(DaysVisitor)
+}
@@ -0,0 +1,134 @@
+//! Changes between two versions of a schedule, independent of how they are presented
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem,
+};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use super::{date_time::dates, day_of_week::DayOfWeek, educator_model::DayStudyEvent};
+
+/// Event matched with its previous version, flags of `new` may explain the change
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModifiedEvent {
+    pub old: DayStudyEvent,
+    pub new: DayStudyEvent,
+    /// Dates of `new` missing from `old`
+    #[serde(with = "dates")]
+    pub added_dates: BTreeSet<NaiveDate>,
+    /// Dates of `old` missing from `new`
+    #[serde(with = "dates")]
+    pub removed_dates: BTreeSet<NaiveDate>,
+}
+
+impl ModifiedEvent {
+    pub fn new(old: DayStudyEvent, new: DayStudyEvent) -> Self {
+        ModifiedEvent {
+            added_dates: new.dates.difference(&old.dates).copied().collect(),
+            removed_dates: old.dates.difference(&new.dates).copied().collect(),
+            old,
+            new,
+        }
+    }
+}
+
+/// Event which moved from one watched educator to another
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reassignment {
+    /// Event as the new educator has it
+    pub event: DayStudyEvent,
+    /// Display names of educators
+    pub from: String,
+    pub to: String,
+}
+
+/// Changes of events of a day present in both versions
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EventsDiff {
+    /// New events marked as cancelled, including those matched with their previous version
+    pub cancelled: Vec<DayStudyEvent>,
+    pub modified: Vec<ModifiedEvent>,
+    pub reassigned: Vec<Reassignment>,
+    pub added: Vec<DayStudyEvent>,
+    pub removed: Vec<DayStudyEvent>,
+}
+
+impl EventsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.cancelled.is_empty()
+            && self.modified.is_empty()
+            && self.reassigned.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DayChange {
+    /// Day which had no events before
+    Added {
+        events: Vec<DayStudyEvent>,
+    },
+    /// Day which is missing from the new version
+    Removed {
+        events: Vec<DayStudyEvent>,
+    },
+    Modified(EventsDiff),
+}
+
+impl DayChange {
+    /// Changes of the day as if it was present in both versions
+    pub fn as_modified(&mut self) -> &mut EventsDiff {
+        match self {
+            DayChange::Added { events } => {
+                *self = DayChange::Modified(EventsDiff {
+                    added: mem::take(events),
+                    ..Default::default()
+                })
+            }
+            DayChange::Removed { events } => {
+                *self = DayChange::Modified(EventsDiff {
+                    removed: mem::take(events),
+                    ..Default::default()
+                })
+            }
+            DayChange::Modified(_) => {}
+        }
+        match self {
+            DayChange::Modified(diff) => diff,
+            _ => unreachable!("the day was just turned into a modified one"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DayDiff {
+    /// Name of the day as the API gave it, like "Понедельник" or "понедельник, 14 октября"
+    pub day_string: String,
+    #[serde(flatten)]
+    pub change: DayChange,
+}
+
+/// Changes of a single schedule, only changed days are present
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ScheduleDiff {
+    pub days: BTreeMap<DayOfWeek, DayDiff>,
+    /// Schedule of a new semester replaced the old one, days aren't diffed then
+    pub new_semester: bool,
+}
+
+impl ScheduleDiff {
+    /// Notice about a new semester instead of changes of every event
+    pub fn new_semester() -> Self {
+        ScheduleDiff {
+            days: BTreeMap::new(),
+            new_semester: true,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.days.is_empty() && !self.new_semester
+    }
+}
@@ -1,45 +1,115 @@
 //! Module with educator model compatible with timetable.spbu.ru's REST API
 use std::collections::BTreeSet;
 
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+use super::{
+    date_time::{dates, EventTime, TimeInterval},
+    day_of_week::{deserialize_days, Days},
+    ScheduleKey, Timetable,
+};
+
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[serde(rename_all = "PascalCase")]
 pub struct ContingentUnitName {
     pub item1: String,
     pub item2: String,
 }
 
-#[derive(Deserialize, Debug, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[serde(rename_all = "PascalCase")]
 pub struct EventLocation {
     pub display_name: String,
 }
 
-#[derive(Deserialize, Debug, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+/// Event of a day, shared by educator and group schedules
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "PascalCase")]
 pub struct DayStudyEvent {
-    pub start: String,
-    pub end: String,
+    pub start: EventTime,
+    pub end: EventTime,
     pub subject: String,
-    pub time_interval_string: String,
-    pub dates: Vec<String>,
+    #[serde(rename = "TimeIntervalString")]
+    pub time_interval: TimeInterval,
+    /// Absent in group schedules
+    #[serde(default, with = "dates")]
+    pub dates: BTreeSet<NaiveDate>,
+    /// Absent in classroom schedules
+    #[serde(default)]
     pub event_locations: BTreeSet<EventLocation>,
+    /// Absent in group schedules
+    #[serde(default)]
     pub contingent_unit_names: BTreeSet<ContingentUnitName>,
+    /// Present only in group and classroom schedules
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub educators_display_text: String,
+    #[serde(default)]
+    pub is_cancelled: bool,
+    /// Set by the dean's office when the event was moved to another time
+    #[serde(default)]
+    pub time_was_changed: bool,
+    /// Set by the dean's office when the event was moved to another room
+    #[serde(default)]
+    pub locations_were_changed: bool,
+    /// Set by the dean's office when the event got other educators
+    #[serde(default)]
+    pub educators_were_reassigned: bool,
 }
 
-#[derive(Deserialize, Debug, Serialize, PartialEq)]
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq, Default)]
 #[serde(rename_all = "PascalCase")]
 pub struct EducatorDay {
+    /// Absent in educator schedules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day: Option<NaiveDateTime>,
     pub day_string: String,
+    /// Absent in group schedules
+    #[serde(default)]
     pub day_study_events_count: u8,
     pub day_study_events: BTreeSet<DayStudyEvent>,
 }
 
-#[derive(Deserialize, Debug, Serialize, PartialEq)]
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq)]
 #[serde(rename_all = "PascalCase")]
 pub struct EducatorEvents {
     pub educator_long_display_text: String,
     pub educator_master_id: u32,
-    pub educator_events_days: [EducatorDay; 6],
+    /// Days the API listed, usually Monday to Saturday
+    #[serde(deserialize_with = "deserialize_days")]
+    pub educator_events_days: Days,
+    /// Monday of the week which events describe, not a part of the API response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub week_start: Option<NaiveDate>,
+}
+
+impl Timetable for EducatorEvents {
+    type Id = u32;
+
+    fn key(&self) -> ScheduleKey {
+        (self.educator_master_id, self.week_start)
+    }
+
+    fn display_name(&self) -> &str {
+        &self.educator_long_display_text
+    }
+
+    fn days(&self) -> &Days {
+        &self.educator_events_days
+    }
+}
+
+/// Response of educators search endpoint
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct EducatorSearchResults {
+    pub educators: Vec<FoundEducator>,
+}
+
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct FoundEducator {
+    pub id: u32,
+    pub display_name: String,
+    pub full_name: String,
 }
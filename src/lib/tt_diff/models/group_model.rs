@@ -0,0 +1,36 @@
+//! Module with group model compatible with timetable.spbu.ru's REST API
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    day_of_week::{deserialize_days, Days},
+    ScheduleKey, Timetable,
+};
+
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct GroupEvents {
+    pub student_group_id: u32,
+    pub student_group_display_name: String,
+    /// Monday of the week which events describe
+    pub week_monday: NaiveDate,
+    /// Unlike educators, only days with events are listed
+    #[serde(deserialize_with = "deserialize_days")]
+    pub days: Days,
+}
+
+impl Timetable for GroupEvents {
+    type Id = u32;
+
+    fn key(&self) -> ScheduleKey {
+        (self.student_group_id, Some(self.week_monday))
+    }
+
+    fn display_name(&self) -> &str {
+        &self.student_group_display_name
+    }
+
+    fn days(&self) -> &Days {
+        &self.days
+    }
+}
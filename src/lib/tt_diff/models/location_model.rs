@@ -0,0 +1,40 @@
+//! Module with classroom model compatible with timetable.spbu.ru's REST API
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    day_of_week::{deserialize_days, Days},
+    ScheduleKey, Timetable,
+};
+
+#[derive(Deserialize, Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct LocationEvents {
+    /// Oid the classroom was requested by, not a part of the API response
+    #[serde(default)]
+    pub oid: String,
+    #[serde(rename = "DisplayName1")]
+    pub display_name: String,
+    /// Like groups, only days with events are listed
+    #[serde(deserialize_with = "deserialize_days")]
+    pub days: Days,
+    /// Monday of the week which events describe, not a part of the API response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub week_start: Option<NaiveDate>,
+}
+
+impl Timetable for LocationEvents {
+    type Id = String;
+
+    fn key(&self) -> ScheduleKey<String> {
+        (self.oid.clone(), self.week_start)
+    }
+
+    fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    fn days(&self) -> &Days {
+        &self.days
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use tokio::{
+    sync::Mutex,
+    time::{sleep_until, Instant},
+};
+
+/// Spaces requests evenly, so that no more than `requests_per_second` are started
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Non-positive `requests_per_second` disables limiting
+    pub fn new(requests_per_second: f64) -> Self {
+        let interval = if requests_per_second > 0.0 {
+            Duration::from_secs_f64(1.0 / requests_per_second)
+        } else {
+            Duration::ZERO
+        };
+        RateLimiter {
+            interval,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the next request may be sent
+    pub async fn wait(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        sleep_until(slot).await;
+    }
+
+    /// Postpones all following requests, e.g. when the server asks to slow down
+    pub async fn pause_for(&self, delay: Duration) {
+        let mut next_slot = self.next_slot.lock().await;
+        *next_slot = (*next_slot).max(Instant::now() + delay);
+    }
+}
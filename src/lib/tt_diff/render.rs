@@ -0,0 +1,296 @@
+//! Letters describing schedule diffs, rendered as HTML and plain text from templates.
+//! Built-in templates live in `templates/` and can be replaced by files set in config
+use std::{collections::BTreeMap, error::Error, fs};
+
+use chrono::{Datelike, Days, NaiveDate};
+use minijinja::{
+    context, escape_formatter, value::Kwargs, AutoEscape, Environment, ErrorKind, Output, State,
+    Value,
+};
+use serde::{Deserialize, Serialize};
+
+use super::grid::WeekGrid;
+use super::models::{
+    date_time::format_date,
+    day_of_week::{self, DayOfWeek},
+    diff_model::ScheduleDiff,
+    ChangedSchedules, Schedule, ScheduleKey, Subscriptions, TemplatePaths, Timetable, User,
+};
+use crate::{html, locale::Locale};
+
+const BUILT_IN_TEMPLATES: [(&str, &str); 6] = [
+    ("macros.html", include_str!("templates/macros.html")),
+    ("changes.html", include_str!("templates/changes.html")),
+    ("welcome.html", include_str!("templates/welcome.html")),
+    ("macros.txt", include_str!("templates/macros.txt")),
+    ("changes.txt", include_str!("templates/changes.txt")),
+    ("welcome.txt", include_str!("templates/welcome.txt")),
+];
+
+/* template filter formatting dates like "2024-10-14" or "14.10.2024" with chrono's format */
+fn date_filter(value: &str, format: Option<&str>) -> Result<String, minijinja::Error> {
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%d.%m.%Y"))
+        .map_err(|_| {
+            minijinja::Error::new(
+                ErrorKind::InvalidOperation,
+                format!("invalid date \"{}\"", value),
+            )
+        })?;
+    Ok(match format {
+        Some(format) => date.format(format).to_string(),
+        None => format_date(&date),
+    })
+}
+
+/* prints values like the default formatter, but escapes strings of HTML letters with `html::escape` */
+fn formatter(out: &mut Output, state: &State, value: &Value) -> Result<(), minijinja::Error> {
+    match value.as_str() {
+        Some(text) if state.auto_escape() == AutoEscape::Html && !value.is_safe() => out
+            .write_str(&html::escape(text))
+            .map_err(|_| minijinja::Error::new(ErrorKind::WriteFailure, "can't write letter")),
+        _ => escape_formatter(out, state, value),
+    }
+}
+
+/* template function translating `key` into the language of the letter, Russian outside of letters.
+Keyword arguments fill placeholders of the text, like `t("reassigned", from=..., to=...)`,
+and are escaped in HTML letters unless they are markup of macros */
+fn translate(state: &State, key: &str, args: Kwargs) -> Result<Value, minijinja::Error> {
+    let locale = match state.lookup("locale") {
+        Some(locale) => Locale::deserialize(locale)?,
+        None => Locale::default(),
+    };
+    let mut text = locale.tr(key).to_string();
+    for name in args.args() {
+        let value: Value = args.get(name)?;
+        text = text.replace(&format!("{{{}}}", name), &state.format(value)?);
+    }
+    Ok(Value::from_safe_string(text))
+}
+
+#[derive(Serialize)]
+struct Week {
+    monday: NaiveDate,
+    sunday: NaiveDate,
+    /// Date of every weekday, letters name days by weekday rather than by the API's day strings
+    dates: BTreeMap<DayOfWeek, NaiveDate>,
+}
+
+impl Week {
+    fn starting(monday: NaiveDate) -> Self {
+        let dates = (0..7)
+            .map(|offset| monday + Days::new(offset))
+            .map(|date| (date.weekday().into(), date))
+            .collect();
+        Week {
+            monday,
+            sunday: monday + Days::new(6),
+            dates,
+        }
+    }
+}
+
+/// Schedule as templates see it
+#[derive(Serialize)]
+struct ScheduleView<'a, T> {
+    /// "educator", "group" or "location"
+    kind: &'static str,
+    name: &'a str,
+    /// Absent for snapshots written before weeks were tracked
+    week: Option<Week>,
+    days: &'a day_of_week::Days,
+    /// Schedule as the API gave it
+    schedule: &'a T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<&'a ScheduleDiff>,
+    /// Present for users who asked for a table of the week
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grid: Option<WeekGrid>,
+}
+
+fn schedule_view<'a, Id, T: Timetable + Serialize>(
+    kind: &'static str,
+    key: &ScheduleKey<Id>,
+    schedule: &'a T,
+    diff: Option<&'a ScheduleDiff>,
+    grid: bool,
+) -> Value {
+    Value::from_serialize(ScheduleView {
+        kind,
+        name: schedule.display_name(),
+        week: key.1.map(Week::starting),
+        days: schedule.days(),
+        schedule,
+        diff,
+        grid: diff
+            .filter(|diff| grid && !diff.new_semester)
+            .map(|diff| WeekGrid::new(schedule.days(), diff)),
+    })
+}
+
+/* changed schedules the user watches, educators first, then groups and locations */
+fn watched_changes(changed: &ChangedSchedules, user: &User) -> Vec<Value> {
+    let educators = changed
+        .educators
+        .iter()
+        .filter(|(key, _)| user.watch_educators.contains(&key.0))
+        .map(|(key, (events, diff))| {
+            schedule_view("educator", key, *events, Some(diff), user.weekly_grid)
+        });
+    let groups = changed
+        .groups
+        .iter()
+        .filter(|(key, _)| user.watch_groups.contains(&key.0))
+        .map(|(key, (events, diff))| {
+            schedule_view("group", key, *events, Some(diff), user.weekly_grid)
+        });
+    let locations = changed
+        .locations
+        .iter()
+        .filter(|(key, _)| user.watch_locations.contains(&key.0))
+        .map(|(key, (events, diff))| {
+            schedule_view("location", key, *events, Some(diff), user.weekly_grid)
+        });
+    educators.chain(groups).chain(locations).collect()
+}
+
+/* schedules from `subscriptions` in their current state */
+fn subscribed_schedules(schedule: &Schedule, subscriptions: &Subscriptions) -> Vec<Value> {
+    let educators = schedule
+        .educators
+        .iter()
+        .filter(|(key, _)| subscriptions.educators.contains(&key.0))
+        .map(|(key, events)| schedule_view("educator", key, events, None, false));
+    let groups = schedule
+        .groups
+        .iter()
+        .filter(|(key, _)| subscriptions.groups.contains(&key.0))
+        .map(|(key, events)| schedule_view("group", key, events, None, false));
+    let locations = schedule
+        .locations
+        .iter()
+        .filter(|(key, _)| subscriptions.locations.contains(&key.0))
+        .map(|(key, events)| schedule_view("location", key, events, None, false));
+    educators.chain(groups).chain(locations).collect()
+}
+
+/// Letter rendered both ways, clients showing no HTML fall back to `text`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Letter {
+    pub html: String,
+    pub text: String,
+}
+
+/// Templates of letters sent to users
+pub struct Letters {
+    env: Environment<'static>,
+}
+
+impl Letters {
+    /// Built-in templates, except those replaced by files from `paths`
+    pub fn new(paths: &TemplatePaths) -> Result<Self, Box<dyn Error>> {
+        let mut env = Environment::new();
+        /* lines with block tags shouldn't leave line breaks, HTML letters are single-line */
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        /* texts from the API may hold `<` or `&`, HTML letters escape everything but markup of macros */
+        env.set_auto_escape_callback(|name| match name.ends_with(".html") {
+            true => AutoEscape::Html,
+            false => AutoEscape::None,
+        });
+        env.set_formatter(formatter);
+        env.add_filter("date", date_filter);
+        env.add_function("t", translate);
+        for (name, source) in BUILT_IN_TEMPLATES {
+            match paths.get(name) {
+                Some(path) => env.add_template_owned(name, fs::read_to_string(path)?)?,
+                None => env.add_template(name, source)?,
+            }
+        }
+        Ok(Letters { env })
+    }
+
+    /* renders both `{name}.html` and `{name}.txt` */
+    fn render_letter(&self, name: &str, ctx: Value) -> Result<Letter, minijinja::Error> {
+        let render = |ext| {
+            self.env
+                .get_template(&format!("{}.{}", name, ext))?
+                .render(&ctx)
+        };
+        Ok(Letter {
+            html: render("html")?,
+            text: render("txt")?,
+        })
+    }
+
+    /* macro of `macros.html`, so that parts of letters can be rendered on their own */
+    fn call_macro(&self, name: &str, args: &[Value]) -> Result<String, minijinja::Error> {
+        self.env
+            .get_template("macros.html")?
+            .render_captured(())?
+            .state()
+            .call_macro(name, args)
+    }
+
+    /// HTML description of all changed days of a schedule
+    pub fn render_schedule_diff(&self, diff: &ScheduleDiff) -> Result<String, minijinja::Error> {
+        self.call_macro("schedule_diff", &[Value::from_serialize(diff)])
+    }
+
+    /// Changes of all schedules the user watches, empty if none of them changed
+    pub fn collect_all_tracked_diffs(
+        &self,
+        changed: &ChangedSchedules,
+        user: &User,
+    ) -> Result<String, minijinja::Error> {
+        self.call_macro("changes", &[Value::from(watched_changes(changed, user))])
+    }
+
+    /// Current state of every schedule in `subscriptions`
+    pub fn collect_subscribed_schedules(
+        &self,
+        schedule: &Schedule,
+        subscriptions: &Subscriptions,
+    ) -> Result<String, minijinja::Error> {
+        let schedules = subscribed_schedules(schedule, subscriptions);
+        self.call_macro("subscribed", &[Value::from(schedules)])
+    }
+
+    /// Letter about changes of schedules the user watches, if any of them changed
+    pub fn changes_letter(
+        &self,
+        changed: &ChangedSchedules,
+        user: &User,
+    ) -> Result<Option<Letter>, minijinja::Error> {
+        let schedules = watched_changes(changed, user);
+        if schedules.is_empty() {
+            return Ok(None);
+        }
+        let locale = user.locale;
+        self.render_letter("changes", context! { user, locale, schedules })
+            .map(Some)
+    }
+
+    /// Letter for a new subscriber with current state of newly watched schedules
+    pub fn welcome_letter(
+        &self,
+        schedule: &Schedule,
+        subscriptions: &Subscriptions,
+        user: &User,
+    ) -> Result<Letter, minijinja::Error> {
+        let schedules = subscribed_schedules(schedule, subscriptions);
+        let locale = user.locale;
+        self.render_letter("welcome", context! { user, locale, schedules })
+    }
+}
+
+impl Default for Letters {
+    fn default() -> Self {
+        Letters::new(&TemplatePaths::default()).expect("built-in templates are valid")
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/render_tests.rs"]
+mod tests;
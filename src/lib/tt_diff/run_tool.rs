@@ -1,27 +1,139 @@
-use log::info;
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
 
 use super::{
-    helpers::{generate_diff_messages, get_previous_events, get_users, write_previous_events},
+    debounce::debounce_changes,
+    filters::filter_changes,
+    helpers::{
+        carry_forward_failed, choose_educator, current_subscriptions, generate_all_diffs,
+        get_previous_events, get_previous_schema_report, get_users, local_date, log_fetch_failures,
+        log_schema_report, new_subscriptions, unwatch, write_previous_events, write_schema_report,
+    },
     letter_sender::LetterSender,
-    models::{Args, Config},
+    models::{Args, Config, NewSubscriptions, User},
+    render::Letters,
     schedule_getter::ScheduleGetter,
 };
 
+/// Adds educators from `watch_educator_names` to `watch_educators`.
+/// Names are looked up only when they are missing from `cache`,
+/// returns the cache of names which are still in use
+pub async fn resolve_educator_names<SG: ScheduleGetter>(
+    schedule_getter: &SG,
+    config: &Config,
+    users: &mut [User],
+    cache: &BTreeMap<String, u32>,
+) -> BTreeMap<String, u32> {
+    let names = users
+        .iter()
+        .flat_map(|user| &user.watch_educator_names)
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    let mut resolved = BTreeMap::new();
+    for name in names {
+        if let Some(&id) = cache.get(&name) {
+            resolved.insert(name, id);
+            continue;
+        }
+        match schedule_getter.search_educators(&name, config).await {
+            Ok(candidates) => match choose_educator(&name, &candidates) {
+                Ok(educator) => {
+                    info!(
+                        "Resolved educator name \"{}\" to {} ({})",
+                        name, educator.id, educator.full_name
+                    );
+                    resolved.insert(name, educator.id);
+                }
+                Err(reason) => warn!("Couldn't resolve educator: {}", reason),
+            },
+            Err(error) => warn!("Couldn't search educator \"{}\": {}", name, error),
+        }
+    }
+    for user in users.iter_mut() {
+        let ids = user
+            .watch_educator_names
+            .iter()
+            .filter_map(|name| resolved.get(name))
+            .collect::<Vec<_>>();
+        user.watch_educators.extend(ids);
+    }
+    resolved
+}
+
 pub async fn run<SG: ScheduleGetter, LS: LetterSender>(
     schedule_getter: SG,
     letter_sender: LS,
     args: &Args,
     config: Config,
-) -> () {
-    let users = get_users(&args).unwrap();
-    let educator_events_old = get_previous_events(&args).unwrap();
-    info!("Found {} educators in db", educator_events_old.len());
-    let educator_events_new = schedule_getter.get_schedule(&users).await;
-    let educators_changed = generate_diff_messages(&educator_events_old, &educator_events_new);
+    now: DateTime<Utc>,
+) {
+    let letters = Letters::new(&config.templates).unwrap();
+    let mut users = get_users(args).unwrap();
+    let schedule_old = get_previous_events(args).unwrap();
+    info!(
+        "Found {} educators, {} groups and {} locations in db",
+        schedule_old.educators.len(),
+        schedule_old.groups.len(),
+        schedule_old.locations.len()
+    );
+    let educator_names = resolve_educator_names(
+        &schedule_getter,
+        &config,
+        &mut users,
+        &schedule_old.educator_names,
+    )
+    .await;
+    let (mut schedule_new, report) = schedule_getter.get_schedule(&users, &config, now).await;
+    schedule_new.educator_names = educator_names;
+    carry_forward_failed(&schedule_old, &mut schedule_new, &report.failures);
+    debounce_changes(&config, now, &schedule_old, &mut schedule_new);
+    if !schedule_new.pending.is_empty() {
+        info!(
+            "Changes of {} schedules are held back until confirmed",
+            schedule_new.pending.len()
+        );
+    }
+    let subscriptions = current_subscriptions(&users, &schedule_new);
+    let subscribed = new_subscriptions(schedule_old.subscriptions.as_ref(), &subscriptions);
+    if config.new_subscriptions == NewSubscriptions::Welcome {
+        /* the welcome letter shows current state, so changes aren't sent on top of it */
+        for user in users.iter_mut() {
+            if let Some(new) = subscribed.get(&user.email) {
+                let letter = letters.welcome_letter(&schedule_new, new, user).unwrap();
+                letter_sender.send_welcome_letter(&config, user, &letter);
+                unwatch(user, new);
+            }
+        }
+    }
+    schedule_new.subscriptions = Some(subscriptions);
+    let today = local_date(&config, now);
+    let changed = generate_all_diffs(&schedule_old, &schedule_new, today);
     info!(
-        "Found {} changed educators schedules",
-        educators_changed.len()
+        "Found {} changed educators, {} changed groups and {} changed locations schedules",
+        changed.educators.len(),
+        changed.groups.len(),
+        changed.locations.len()
     );
-    letter_sender.form_and_send_letters(users, config, educators_changed);
-    write_previous_events(&args, educator_events_new).unwrap();
+    let previous_report = get_previous_schema_report(args).unwrap();
+    write_schema_report(args, &report.schema_drift).unwrap();
+    if !report.schema_drift.is_empty() {
+        log_schema_report(&report.schema_drift);
+    }
+    /* the admin hears about every issue once, until it goes away and comes back */
+    let new_issues = report.schema_drift.new_since(&previous_report);
+    if !new_issues.is_empty() {
+        letter_sender.send_schema_report(&config, &new_issues);
+    }
+    let changes = users
+        .into_iter()
+        .map(|user| {
+            let filtered = filter_changes(&changed, &user, today);
+            (user, filtered)
+        })
+        .collect();
+    letter_sender.form_and_send_letters(config, &letters, changes);
+    write_previous_events(args, schedule_new).unwrap();
+    log_fetch_failures(&report.failures);
 }
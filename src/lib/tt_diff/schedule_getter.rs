@@ -1,38 +1,186 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+};
 
-use futures::future;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{stream, StreamExt};
+use itertools::Itertools;
 use log::info;
 use reqwest::Client;
 
 use super::{
-    helpers::get_educator_events_by_id,
-    models::{educator_model::EducatorEvents, User},
+    helpers::{
+        get_educator_events_by_id, get_group_events_by_id, get_location_events_by_id, local_date,
+        search_educators, watched_weeks, with_retries, FetchError,
+    },
+    models::{
+        educator_model::FoundEducator, Config, FetchFailure, FetchReport, Schedule, ScheduleKey,
+        ScheduleRef, User,
+    },
+    rate_limiter::RateLimiter,
+    schema_drift::SchemaChecker,
 };
 
+/* fetches every week of every id, schedules which couldn't be fetched are returned as failures */
+async fn fetch_weeks<Id, T, F, Fut>(
+    config: &Config,
+    ids: BTreeSet<Id>,
+    weeks: &[NaiveDate],
+    fetch: F,
+    schedule_ref: impl Fn(ScheduleKey<Id>) -> ScheduleRef,
+) -> (BTreeMap<ScheduleKey<Id>, T>, Vec<FetchFailure>)
+where
+    Id: Ord + Clone,
+    F: Fn(Id, NaiveDate) -> Fut,
+    Fut: Future<Output = Result<(ScheduleKey<Id>, T), FetchError>>,
+{
+    let (fetch, schedule_ref) = (&fetch, &schedule_ref);
+    stream::iter(
+        ids.into_iter()
+            .cartesian_product(weeks.iter())
+            .map(|(id, &week)| async move {
+                with_retries(config, || fetch(id.clone(), week))
+                    .await
+                    .map_err(|error| FetchFailure {
+                        schedule: schedule_ref((id, Some(week))),
+                        error: error.to_string(),
+                    })
+            }),
+    )
+    .buffer_unordered(config.max_concurrent_requests.max(1))
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .partition_result()
+}
+
 // probably do smth about this warning later
 #[allow(async_fn_in_trait)]
 pub trait ScheduleGetter {
-    async fn get_schedule(&self, users: &Vec<User>) -> BTreeMap<u32, EducatorEvents>;
+    /// Fetches all watched schedules. Schedules which couldn't be fetched
+    /// are left out of the result and reported as failures instead.
+    /// Weeks to fetch are counted from `now`
+    async fn get_schedule(
+        &self,
+        users: &[User],
+        config: &Config,
+        now: DateTime<Utc>,
+    ) -> (Schedule, FetchReport);
+
+    /// Finds educators whose name starts with `name`
+    async fn search_educators(
+        &self,
+        name: &str,
+        config: &Config,
+    ) -> Result<Vec<FoundEducator>, FetchError>;
 }
 
 impl ScheduleGetter for Client {
-    async fn get_schedule(&self, users: &Vec<User>) -> BTreeMap<u32, EducatorEvents> {
+    async fn get_schedule(
+        &self,
+        users: &[User],
+        config: &Config,
+        now: DateTime<Utc>,
+    ) -> (Schedule, FetchReport) {
         let watched_educators = users
             .iter()
             .flat_map(|user| &user.watch_educators)
             .cloned()
             .collect::<BTreeSet<_>>();
-        /* Collect new info from timetable about all watched educators */
-        let educator_events_new = future::join_all(
-            watched_educators
-                .into_iter()
-                .map(|id| get_educator_events_by_id(&self, id)),
+        let watched_groups = users
+            .iter()
+            .flat_map(|user| &user.watch_groups)
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let watched_locations = users
+            .iter()
+            .flat_map(|user| &user.watch_locations)
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let weeks = watched_weeks(local_date(config, now), config.weeks_ahead);
+        let rate_limiter = &RateLimiter::new(config.requests_per_second);
+        let schema_checker = &SchemaChecker::new(config.lenient_parsing);
+        /* Collect new info from timetable about all watched educators, groups and locations for every week */
+        let (educator_events_new, mut failures) = fetch_weeks(
+            config,
+            watched_educators,
+            &weeks,
+            |id, week| {
+                get_educator_events_by_id(
+                    self,
+                    rate_limiter,
+                    schema_checker,
+                    &config.api_base_url,
+                    id,
+                    week,
+                )
+            },
+            ScheduleRef::Educator,
         )
-        .await
-        .into_iter()
-        .collect::<Result<BTreeMap<_, _>, _>>()
-        .unwrap();
+        .await;
         info!("Collected {} educator events", educator_events_new.len());
-        return educator_events_new;
+        let (group_events_new, group_failures) = fetch_weeks(
+            config,
+            watched_groups,
+            &weeks,
+            |id, week| {
+                get_group_events_by_id(
+                    self,
+                    rate_limiter,
+                    schema_checker,
+                    &config.api_base_url,
+                    id,
+                    week,
+                )
+            },
+            ScheduleRef::Group,
+        )
+        .await;
+        info!("Collected {} group events", group_events_new.len());
+        failures.extend(group_failures);
+        let (location_events_new, location_failures) = fetch_weeks(
+            config,
+            watched_locations,
+            &weeks,
+            |oid, week| async move {
+                get_location_events_by_id(
+                    self,
+                    rate_limiter,
+                    schema_checker,
+                    &config.api_base_url,
+                    &oid,
+                    week,
+                )
+                .await
+            },
+            ScheduleRef::Location,
+        )
+        .await;
+        info!("Collected {} location events", location_events_new.len());
+        failures.extend(location_failures);
+        let schedule = Schedule {
+            educators: educator_events_new,
+            groups: group_events_new,
+            locations: location_events_new,
+            ..Default::default()
+        };
+        let report = FetchReport {
+            failures,
+            schema_drift: schema_checker.take_report(),
+        };
+        (schedule, report)
+    }
+
+    async fn search_educators(
+        &self,
+        name: &str,
+        config: &Config,
+    ) -> Result<Vec<FoundEducator>, FetchError> {
+        let rate_limiter = RateLimiter::new(config.requests_per_second);
+        with_retries(config, || {
+            search_educators(self, &rate_limiter, &config.api_base_url, name)
+        })
+        .await
     }
 }
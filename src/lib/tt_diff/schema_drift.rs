@@ -0,0 +1,276 @@
+//! Parsing of timetable API responses which notices when they stop matching our models
+use std::{collections::BTreeSet, sync::Mutex};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use serde_path_to_error::Segment;
+
+use super::models::{SchemaIssue, SchemaIssueKind, SchemaReport};
+
+/// How many times a single response may be repaired before giving up on it
+const MAX_REPAIRS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    Index(usize),
+    Key(String),
+}
+
+/* path like `EducatorEventsDays[].DayStudyEvents[].Subject`, so that issues of all elements look the same */
+fn format_path(steps: &[Step]) -> String {
+    let mut path = String::new();
+    for step in steps {
+        match step {
+            Step::Index(_) => path.push_str("[]"),
+            Step::Key(key) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+            }
+        }
+    }
+    path
+}
+
+fn ignored_path_steps(path: &serde_ignored::Path, steps: &mut Vec<Step>) {
+    match path {
+        serde_ignored::Path::Root => {}
+        serde_ignored::Path::Seq { parent, index } => {
+            ignored_path_steps(parent, steps);
+            steps.push(Step::Index(*index));
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            ignored_path_steps(parent, steps);
+            steps.push(Step::Key(key.clone()));
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => ignored_path_steps(parent, steps),
+    }
+}
+
+fn error_path_steps(path: &serde_path_to_error::Path) -> Vec<Step> {
+    path.iter()
+        .filter_map(|segment| match segment {
+            Segment::Seq { index } => Some(Step::Index(*index)),
+            Segment::Map { key } => Some(Step::Key(key.clone())),
+            Segment::Enum { .. } | Segment::Unknown => None,
+        })
+        .collect()
+}
+
+fn value_at<'a>(value: &'a mut Value, steps: &[Step]) -> Option<&'a mut Value> {
+    steps.iter().try_fold(value, |value, step| match step {
+        Step::Index(index) => value.get_mut(*index),
+        Step::Key(key) => value.get_mut(key),
+    })
+}
+
+/* empty value of the type serde expected, judging by its error message */
+fn default_for(expected: &str) -> Option<Value> {
+    if expected.contains("string") {
+        Some(Value::String(String::new()))
+    } else if expected.contains("sequence") {
+        Some(Value::Array(Vec::new()))
+    } else if expected.contains("map") || expected.starts_with("struct") {
+        Some(Value::Object(Default::default()))
+    } else if expected.contains("boolean") {
+        Some(Value::Bool(false))
+    } else if expected.starts_with('u') || expected.starts_with('i') || expected.starts_with('f') {
+        Some(Value::from(0))
+    } else {
+        None
+    }
+}
+
+/* the same value converted to the type serde expected, if that's possible without guessing */
+fn coerce(value: &Value, expected: &str) -> Option<Value> {
+    match value {
+        Value::Number(number) if expected.contains("string") => {
+            Some(Value::String(number.to_string()))
+        }
+        Value::Bool(flag) if expected.contains("string") => Some(Value::String(flag.to_string())),
+        Value::String(text) if expected.contains("boolean") => text.parse().ok().map(Value::Bool),
+        Value::String(text) if default_for(expected) == Some(Value::from(0)) => {
+            text.parse::<i64>().ok().map(Value::from)
+        }
+        _ => None,
+    }
+}
+
+fn missing_field(message: &str) -> Option<&str> {
+    message
+        .strip_prefix("missing field `")?
+        .split_once('`')
+        .map(|(field, _)| field)
+}
+
+fn expected_type(message: &str) -> Option<&str> {
+    message
+        .strip_prefix("invalid type: ")?
+        .split_once(", expected ")
+        .map(|(_, expected)| expected)
+}
+
+/* message without the value it quotes, like "invalid type: string, expected u32",
+so that the same drift of many responses is a single issue */
+fn without_value(message: &str) -> String {
+    let Some(quote) = message.find(['"', '`']) else {
+        return message.to_string();
+    };
+    let problem = message[..quote].trim_end();
+    match message.split_once(", expected ") {
+        Some((_, expected)) => format!("{}, expected {}", problem, expected),
+        None => problem.to_string(),
+    }
+}
+
+fn describe(steps: &[Step], message: &str) -> SchemaIssue {
+    match missing_field(message) {
+        Some(field) => {
+            let mut field_steps = steps.to_vec();
+            field_steps.push(Step::Key(field.to_string()));
+            SchemaIssue {
+                kind: SchemaIssueKind::Missing,
+                path: format_path(&field_steps),
+                detail: String::new(),
+            }
+        }
+        None => SchemaIssue {
+            kind: SchemaIssueKind::Retyped,
+            path: format_path(steps),
+            detail: without_value(message),
+        },
+    }
+}
+
+/* leaves out the innermost list element containing the broken value */
+fn drop_element(value: &mut Value, steps: &[Step], message: &str) -> Option<SchemaIssue> {
+    let position = steps
+        .iter()
+        .rposition(|step| matches!(step, Step::Index(_)))?;
+    let Step::Index(index) = steps[position] else {
+        return None;
+    };
+    let list = value_at(value, &steps[..position])?.as_array_mut()?;
+    if index >= list.len() {
+        return None;
+    }
+    list.remove(index);
+    Some(SchemaIssue {
+        kind: SchemaIssueKind::Dropped,
+        path: format_path(&steps[..=position]),
+        detail: without_value(message),
+    })
+}
+
+/* changes `value` so that the error goes away, returns the issue worth reporting if there is one.
+Missing fields are stubbed with null first, as their type is known only from the next error */
+fn repair(
+    value: &mut Value,
+    steps: &[Step],
+    message: &str,
+    stubs: &mut BTreeSet<Vec<Step>>,
+) -> Result<Option<SchemaIssue>, SchemaIssue> {
+    let issue = describe(steps, message);
+    if let Some(field) = missing_field(message) {
+        let mut field_steps = steps.to_vec();
+        field_steps.push(Step::Key(field.to_string()));
+        if let Some(Value::Object(object)) = value_at(value, steps) {
+            if stubs.insert(field_steps) {
+                object.insert(field.to_string(), Value::Null);
+                return Ok(Some(issue));
+            }
+        }
+    } else if let Some(expected) = expected_type(message) {
+        let is_stub = stubs.remove(steps);
+        if let Some(target) = value_at(value, steps) {
+            if let Some(fixed) = coerce(target, expected).or_else(|| default_for(expected)) {
+                *target = fixed;
+                return Ok((!is_stub).then_some(issue));
+            }
+        }
+    }
+    drop_element(value, steps, message).map(Some).ok_or(issue)
+}
+
+/// Parses API responses and collects their differences from our models
+pub struct SchemaChecker {
+    lenient: bool,
+    report: Mutex<SchemaReport>,
+}
+
+impl SchemaChecker {
+    pub fn new(lenient: bool) -> Self {
+        SchemaChecker {
+            lenient,
+            report: Mutex::new(SchemaReport::default()),
+        }
+    }
+
+    /// Issues collected so far, the checker starts over with an empty report
+    pub fn take_report(&self) -> SchemaReport {
+        std::mem::take(&mut self.report.lock().unwrap())
+    }
+
+    fn record(&self, endpoint: &str, issues: Vec<SchemaIssue>) {
+        if issues.is_empty() {
+            return;
+        }
+        self.report
+            .lock()
+            .unwrap()
+            .endpoints
+            .entry(endpoint.to_string())
+            .or_default()
+            .extend(issues);
+    }
+
+    /// Parses `body` as `T`, noting unknown fields. A response which doesn't fit
+    /// the model fails, unless lenient parsing is on and the response can be repaired
+    pub fn parse<T: DeserializeOwned>(&self, endpoint: &str, body: &[u8]) -> Result<T, String> {
+        let mut value: Value =
+            serde_json::from_slice(body).map_err(|error| format!("invalid JSON: {}", error))?;
+        let mut issues = Vec::new();
+        let mut stubs = BTreeSet::new();
+        for _ in 0..=MAX_REPAIRS {
+            let mut unknown = Vec::new();
+            let mut note_unknown = |path: serde_ignored::Path| {
+                let mut steps = Vec::new();
+                ignored_path_steps(&path, &mut steps);
+                unknown.push(format_path(&steps));
+            };
+            let deserializer = serde_ignored::Deserializer::new(value.clone(), &mut note_unknown);
+            let error = match serde_path_to_error::deserialize::<_, T>(deserializer) {
+                Ok(parsed) => {
+                    issues.extend(unknown.into_iter().map(|path| SchemaIssue {
+                        kind: SchemaIssueKind::Unknown,
+                        path,
+                        detail: String::new(),
+                    }));
+                    self.record(endpoint, issues);
+                    return Ok(parsed);
+                }
+                Err(error) => error,
+            };
+            let steps = error_path_steps(error.path());
+            let message = error.inner().to_string();
+            let repaired = if self.lenient {
+                repair(&mut value, &steps, &message, &mut stubs)
+            } else {
+                Err(describe(&steps, &message))
+            };
+            match repaired {
+                Ok(issue) => issues.extend(issue),
+                Err(issue) => {
+                    issues.push(issue);
+                    self.record(endpoint, issues);
+                    return Err(format!("{} at {}", message, error.path()));
+                }
+            }
+        }
+        self.record(endpoint, issues);
+        Err(format!("more than {} schema errors", MAX_REPAIRS))
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::NaiveDate;
+
+use crate::tt_diff::grid::{CellStatus, GridEntry};
+use crate::tt_diff::helpers::generate_all_diffs;
+use crate::tt_diff::models::{
+    day_of_week::{DayOfWeek, Days},
+    diff_model::{DayChange, DayDiff, EventsDiff, ModifiedEvent, Reassignment},
+    educator_model::{
+        ContingentUnitName, DayStudyEvent, EducatorDay, EducatorEvents, EventLocation,
+    },
+    group_model::GroupEvents,
+};
+
+use super::*;
+
+fn format_event_as_string(event: &DayStudyEvent) -> String {
+    Letters::default()
+        .call_macro("event", &[Value::from_serialize(event)])
+        .unwrap()
+}
+
+fn format_changed_event(event: &ModifiedEvent) -> String {
+    Letters::default()
+        .call_macro("changed_event", &[Value::from_serialize(event)])
+        .unwrap()
+}
+
+fn render_schedule_diff(diff: &ScheduleDiff) -> String {
+    Letters::default().render_schedule_diff(diff).unwrap()
+}
+
+fn lecture(time_interval: &str, location: &str) -> DayStudyEvent {
+    let (start, end) = time_interval.split_once('–').unwrap();
+    DayStudyEvent {
+        time_interval: time_interval.parse().unwrap(),
+        start: start.parse().unwrap(),
+        end: end.parse().unwrap(),
+        subject: "Матлогика".to_string(),
+        dates: BTreeSet::new(),
+        event_locations: BTreeSet::from([EventLocation {
+            display_name: location.to_string(),
+        }]),
+        contingent_unit_names: BTreeSet::new(),
+        educators_display_text: String::new(),
+        is_cancelled: false,
+        time_was_changed: false,
+        locations_were_changed: false,
+        educators_were_reassigned: false,
+    }
+}
+
+#[test]
+fn format_event_as_string1() {
+    let test_event = DayStudyEvent {
+        time_interval: "09:30–11:00".parse().unwrap(),
+        start: "09:30".parse().unwrap(),
+        end: "11:00".parse().unwrap(),
+        subject: "Матлогика".to_string(),
+        dates: BTreeSet::from([NaiveDate::from_ymd_opt(2025, 9, 1).unwrap()]),
+        event_locations: BTreeSet::from([EventLocation {
+            display_name: "Университетский пр. 28Д".to_string(),
+        }]),
+        contingent_unit_names: BTreeSet::from([ContingentUnitName {
+            item1: "Группа".to_string(),
+            item2: "23.Б15-мм".to_string(),
+        }]),
+        educators_display_text: String::new(),
+        is_cancelled: false,
+        time_was_changed: false,
+        locations_were_changed: false,
+        educators_were_reassigned: false,
+    };
+    let formatted_event = format_event_as_string(&test_event);
+    assert_eq!(formatted_event, "    <b>Предмет:</b> Матлогика<br>    <b>Время:</b> 09:30–11:00<br>    <b>Даты:</b> 01.09.2025<br>    <b>Места:</b> Университетский пр. 28Д<br>    <b>Направления:</b> Группа 23.Б15-мм<br>")
+}
+
+#[test]
+fn render_schedule_diff_orders_sections() {
+    let mut moved = lecture("09:30–11:05", "Университетский пр. 28Д, 1506");
+    moved.locations_were_changed = true;
+    let mut cancelled = lecture("13:40–15:15", "Университетский пр. 28Д");
+    cancelled.is_cancelled = true;
+    let diff = ScheduleDiff {
+        days: BTreeMap::from([
+            (
+                DayOfWeek::Monday,
+                DayDiff {
+                    day_string: "Понедельник".to_string(),
+                    change: DayChange::Modified(EventsDiff {
+                        cancelled: vec![cancelled],
+                        modified: vec![
+                            ModifiedEvent::new(
+                                lecture("11:15–12:50", "Университетский пр. 28Д"),
+                                lecture("11:40–13:15", "Университетский пр. 28Д"),
+                            ),
+                            ModifiedEvent::new(
+                                lecture("09:30–11:05", "Университетский пр. 28Д"),
+                                moved,
+                            ),
+                        ],
+                        ..Default::default()
+                    }),
+                },
+            ),
+            (
+                DayOfWeek::Tuesday,
+                DayDiff {
+                    day_string: "Вторник".to_string(),
+                    change: DayChange::Removed {
+                        events: vec![lecture("09:30–11:05", "Университетский пр. 28Д")],
+                    },
+                },
+            ),
+        ]),
+        ..Default::default()
+    };
+    assert_eq!(render_schedule_diff(&diff), "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:red;\">Отменено:</em><br>    <b>Предмет:</b> Матлогика<br>    <b>Время:</b> 13:40–15:15<br>    <b>Места:</b> Университетский пр. 28Д<br><br><em style=\"color:orange;\">Аудитория изменена учебным отделом:</em><br>    <b>Предмет:</b> Матлогика<br>    <b>Время:</b> 09:30–11:05<br>    <b>Места:</b> Университетский пр. 28Д → Университетский пр. 28Д, 1506<br><br><em style=\"color:orange;\">Изменённые события:</em><br>    <b>Предмет:</b> Матлогика<br>    <b>Время:</b> 11:15–12:50 → 11:40–13:15<br><br><b><font size=\"5\">Вторник:</font></b><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> Матлогика<br>    <b>Время:</b> 09:30–11:05<br>    <b>Места:</b> Университетский пр. 28Д<br>");
+    assert_eq!(render_schedule_diff(&ScheduleDiff::default()), "");
+}
+
+#[test]
+fn schedule_diff_is_serializable() {
+    let diff = ScheduleDiff {
+        days: BTreeMap::from([(
+            DayOfWeek::Wednesday,
+            DayDiff {
+                day_string: "Среда".to_string(),
+                change: DayChange::Added {
+                    events: vec![lecture("13:40–15:15", "Университетский пр. 28Д")],
+                },
+            },
+        )]),
+        ..Default::default()
+    };
+    let written = serde_json::to_value(&diff).unwrap();
+    assert_eq!(written["days"]["Wednesday"]["kind"], "added");
+    assert_eq!(written["days"]["Wednesday"]["day_string"], "Среда");
+    assert_eq!(
+        written["days"]["Wednesday"]["events"][0]["TimeIntervalString"],
+        "13:40–15:15"
+    );
+}
+
+#[test]
+fn changed_dates_are_listed_one_by_one() {
+    let date = |day, month| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+    let mut old = lecture("09:30–11:05", "Университетский пр. 28Д");
+    old.dates = BTreeSet::from([date(31, 10), date(7, 11)]);
+    let mut new = old.clone();
+    new.dates = BTreeSet::from([date(31, 10), date(14, 11)]);
+
+    let event = ModifiedEvent::new(old, new);
+    assert_eq!(event.added_dates, BTreeSet::from([date(14, 11)]));
+    assert_eq!(event.removed_dates, BTreeSet::from([date(7, 11)]));
+    assert_eq!(format_changed_event(&event), "    <b>Предмет:</b> Матлогика<br>    <b>Время:</b> 09:30–11:05<br>    <b>Даты:</b> добавлены 14.11.2024; удалены 07.11.2024<br>");
+
+    /* order of dates in a response doesn't matter */
+    let reordered: DayStudyEvent = serde_json::from_value(serde_json::json!({
+        "Start": "09:30",
+        "End": "11:05",
+        "Subject": "Матлогика",
+        "TimeIntervalString": "09:30–11:05",
+        "Dates": ["14.11.2024", "31.10.2024"],
+        "EventLocations": [{"DisplayName": "Университетский пр. 28Д"}]
+    }))
+    .unwrap();
+    assert_eq!(reordered, event.new);
+}
+
+#[test]
+fn texts_from_the_api_are_escaped_in_html() {
+    fn keyed<T: Timetable>(schedules: Vec<T>) -> BTreeMap<ScheduleKey<T::Id>, T> {
+        schedules.into_iter().map(|t| (t.key(), t)).collect()
+    }
+    let event = |time: &str, subject: &str, location: &str| {
+        serde_json::json!({
+            "TimeIntervalString": time,
+            "Start": time.split_once('–').unwrap().0,
+            "End": time.split_once('–').unwrap().1,
+            "Subject": subject,
+            "EventLocations": [{ "DisplayName": location }],
+            "ContingentUnitNames": [{ "Item1": "Группа <b>", "Item2": "A&B" }],
+            "EducatorsDisplayText": "O'Neil <i>"
+        })
+    };
+    let educator = |id: u32, name: &str, events: Vec<serde_json::Value>| -> EducatorEvents {
+        serde_json::from_value(serde_json::json!({
+            "EducatorLongDisplayText": name,
+            "EducatorMasterId": id,
+            "EducatorEventsDays": [{ "DayString": "<marquee>", "DayStudyEvents": events }]
+        }))
+        .unwrap()
+    };
+    let group = |events: Vec<serde_json::Value>| -> GroupEvents {
+        serde_json::from_value(serde_json::json!({
+            "StudentGroupId": 394847,
+            "StudentGroupDisplayName": "Группа <23.Б15-мм>",
+            "WeekMonday": "2024-10-14",
+            "Days": if events.is_empty() {
+                serde_json::json!([])
+            } else {
+                serde_json::json!([{ "DayString": "<marquee>", "DayStudyEvents": events }])
+            }
+        }))
+        .unwrap()
+    };
+    let hostile = event(
+        "09:30–11:05",
+        "<script>alert(\"1 & 2\")</script>'",
+        "Ауд. <1> & \"2\"",
+    );
+    let handed_over = event("13:40–15:15", "<s>Теория</s>", "Ауд. 2");
+    let kept = event("11:15–12:50", "Алгебра", "Ауд. 3");
+    let old = Schedule {
+        educators: keyed(vec![
+            educator(
+                1879,
+                "<h1>Малевич</h1>",
+                vec![hostile.clone(), handed_over.clone()],
+            ),
+            educator(1928, "Казимир & Co", vec![kept.clone()]),
+        ]),
+        groups: keyed(vec![group(vec![])]),
+        ..Default::default()
+    };
+    let new = Schedule {
+        educators: keyed(vec![
+            educator(
+                1879,
+                "<h1>Малевич</h1>",
+                vec![event(
+                    "09:30–11:05",
+                    "<script>alert(\"1 & 2\")</script>'",
+                    "</em><a href=\"x\">",
+                )],
+            ),
+            educator(1928, "Казимир & Co", vec![kept, handed_over]),
+        ]),
+        groups: keyed(vec![group(vec![hostile])]),
+        ..Default::default()
+    };
+    let changed = generate_all_diffs(&old, &new, NaiveDate::MIN);
+    let user: User = serde_json::from_value(serde_json::json!({
+        "name": "<img src=x onerror=alert(1)>",
+        "watch_educators": [1879],
+        "watch_groups": [394847],
+        "email": "user@example.com"
+    }))
+    .unwrap();
+
+    let letter = Letters::default()
+        .changes_letter(&changed, &user)
+        .unwrap()
+        .unwrap();
+    for raw in [
+        "<script>",
+        "<1>",
+        "<b> A&B",
+        "<i>",
+        "</em><a",
+        "<s>",
+        "<h1>",
+        "<23.",
+        "<img",
+        "<marquee>",
+    ] {
+        assert!(!letter.html.contains(raw), "{} is not escaped", raw);
+    }
+    assert!(letter
+        .html
+        .starts_with("Уважаемый(ая) &lt;img src=x onerror=alert(1)&gt;!<br><br> В расписании преподавателя <b>&lt;h1&gt;Малевич&lt;/h1&gt;</b> произошли изменения:"));
+    assert!(letter.html.contains("    <b>Места:</b> Ауд. &lt;1&gt; &amp; &quot;2&quot; → &lt;/em&gt;&lt;a href=&quot;x&quot;&gt;<br>"));
+    assert!(letter.html.contains(
+        "<em style=\"color:orange;\">Замена: &lt;h1&gt;Малевич&lt;/h1&gt; → Казимир &amp; Co:</em><br>    <b>Предмет:</b> &lt;s&gt;Теория&lt;/s&gt;<br>"
+    ));
+    assert!(letter.html.contains("В расписании группы <b>Группа &lt;23.Б15-мм&gt;</b> на неделе 14.10–20.10.2024 произошли изменения:"));
+    assert!(letter.html.contains("    <b>Предмет:</b> &lt;script&gt;alert(&quot;1 &amp; 2&quot;)&lt;/script&gt;&#39;<br>    <b>Время:</b> 09:30–11:05<br>    <b>Места:</b> Ауд. &lt;1&gt; &amp; &quot;2&quot;<br>    <b>Преподаватели:</b> O&#39;Neil &lt;i&gt;<br>    <b>Направления:</b> Группа &lt;b&gt; A&amp;B<br>"));
+
+    /* plain text has nothing to escape */
+    assert!(letter
+        .text
+        .contains("    Предмет: <script>alert(\"1 & 2\")</script>'\n"));
+    assert!(letter
+        .text
+        .contains("Замена: <h1>Малевич</h1> → Казимир & Co:\n"));
+}
+
+#[test]
+fn week_grid_marks_changes_in_their_slots() {
+    let event = |time: &str, subject: &str| DayStudyEvent {
+        subject: subject.to_string(),
+        ..lecture(time, "Университетский пр. 28Д")
+    };
+    let mut cancelled = event("15:25–17:00", "Теория графов");
+    cancelled.is_cancelled = true;
+    let moved = ModifiedEvent::new(
+        event("11:15–12:50", "Алгебра"),
+        event("13:40–15:15", "Алгебра"),
+    );
+    let mut relocated_new = event("09:30–11:05", "Матлогика");
+    relocated_new.event_locations = BTreeSet::new();
+    let relocated = ModifiedEvent::new(event("09:30–11:05", "Матлогика"), relocated_new.clone());
+    let day = |events: Vec<DayStudyEvent>| EducatorDay {
+        day_study_events: events.into_iter().collect(),
+        ..Default::default()
+    };
+    let days = Days::from([
+        (
+            DayOfWeek::Monday,
+            day(vec![
+                relocated_new,
+                moved.new.clone(),
+                cancelled.clone(),
+                event("17:10–18:45", "Физкультура"),
+                event("11:15–12:50", "Дискретная математика"),
+            ]),
+        ),
+        (
+            DayOfWeek::Wednesday,
+            day(vec![event("09:30–11:05", "Матлогика")]),
+        ),
+    ]);
+    let diff = ScheduleDiff {
+        days: BTreeMap::from([
+            (
+                DayOfWeek::Monday,
+                DayDiff {
+                    day_string: "Понедельник".to_string(),
+                    change: DayChange::Modified(EventsDiff {
+                        cancelled: vec![cancelled],
+                        modified: vec![relocated, moved],
+                        added: vec![event("11:15–12:50", "Дискретная математика")],
+                        removed: vec![event("17:10–18:45", "Английский")],
+                        ..Default::default()
+                    }),
+                },
+            ),
+            (
+                DayOfWeek::Tuesday,
+                DayDiff {
+                    day_string: "Вторник".to_string(),
+                    change: DayChange::Removed {
+                        events: vec![event("09:30–11:05", "Матлогика")],
+                    },
+                },
+            ),
+        ]),
+        ..Default::default()
+    };
+
+    let grid = WeekGrid::new(&days, &diff);
+    assert_eq!(
+        grid.days,
+        vec![DayOfWeek::Monday, DayOfWeek::Tuesday, DayOfWeek::Wednesday]
+    );
+    let entry = |subject: &str, status| GridEntry {
+        subject: subject.to_string(),
+        status,
+    };
+    let rows = grid
+        .rows
+        .into_iter()
+        .map(|row| (row.time.to_string(), row.cells))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "09:30–11:05".to_string(),
+                vec![
+                    vec![entry("Матлогика", CellStatus::Changed)],
+                    vec![entry("Матлогика", CellStatus::Removed)],
+                    vec![entry("Матлогика", CellStatus::Kept)],
+                ]
+            ),
+            (
+                "11:15–12:50".to_string(),
+                vec![
+                    vec![
+                        entry("Дискретная математика", CellStatus::Added),
+                        entry("Алгебра", CellStatus::Removed)
+                    ],
+                    vec![],
+                    vec![],
+                ]
+            ),
+            (
+                "13:40–15:15".to_string(),
+                vec![vec![entry("Алгебра", CellStatus::Added)], vec![], vec![]]
+            ),
+            (
+                "15:25–17:00".to_string(),
+                vec![
+                    vec![entry("Теория графов", CellStatus::Removed)],
+                    vec![],
+                    vec![]
+                ]
+            ),
+            (
+                "17:10–18:45".to_string(),
+                vec![
+                    vec![
+                        entry("Физкультура", CellStatus::Kept),
+                        entry("Английский", CellStatus::Removed)
+                    ],
+                    vec![],
+                    vec![]
+                ]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn week_grid_strikes_out_events_given_to_other_educators() {
+    let lecture = DayStudyEvent {
+        subject: "Алгебра".to_string(),
+        ..lecture("11:15–12:50", "Университетский пр. 28Д")
+    };
+    let diff = ScheduleDiff {
+        days: BTreeMap::from([(
+            DayOfWeek::Monday,
+            DayDiff {
+                day_string: "Понедельник".to_string(),
+                change: DayChange::Modified(EventsDiff {
+                    reassigned: vec![Reassignment {
+                        event: lecture.clone(),
+                        from: "Энди Уорхол".to_string(),
+                        to: "Казимир Малевич".to_string(),
+                    }],
+                    ..Default::default()
+                }),
+            },
+        )]),
+        ..Default::default()
+    };
+    let monday = |events: Vec<DayStudyEvent>| {
+        Days::from([(
+            DayOfWeek::Monday,
+            EducatorDay {
+                day_study_events: events.into_iter().collect(),
+                ..Default::default()
+            },
+        )])
+    };
+    let statuses = |days: &Days| {
+        WeekGrid::new(days, &diff)
+            .rows
+            .into_iter()
+            .map(|row| row.cells)
+            .collect::<Vec<_>>()
+    };
+    let entry = |status| GridEntry {
+        subject: "Алгебра".to_string(),
+        status,
+    };
+
+    /* the educator who lost the event no longer has it, the one who got it does */
+    assert_eq!(
+        statuses(&monday(Vec::new())),
+        vec![vec![vec![entry(CellStatus::Removed)]]]
+    );
+    assert_eq!(
+        statuses(&monday(vec![lecture.clone()])),
+        vec![vec![vec![entry(CellStatus::Added)]]]
+    );
+}
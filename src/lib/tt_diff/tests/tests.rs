@@ -1,42 +1,115 @@
-use std::{collections::BTreeSet, path::PathBuf};
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
-use crate::tt_diff::models::educator_model::{ContingentUnitName, EducatorDay, EventLocation};
+use chrono::{DateTime, NaiveDate, Utc};
+use mailparse::MailHeaderMap;
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    task::JoinHandle,
+    time::Instant,
+};
+
+use crate::http_client::{build_http_client, HttpSettings};
+use crate::locale::Locale;
+use crate::tt_diff::{
+    debounce::debounce_changes,
+    filters::filter_changes,
+    letter_sender::LetterSender,
+    models::{FetchReport, Filters, SchemaIssue, SchemaIssueKind, TemplatePaths},
+    render::{Letter, Letters},
+    run_tool::{resolve_educator_names, run},
+    schedule_getter::ScheduleGetter,
+    schema_drift::SchemaChecker,
+};
+
+use crate::tt_diff::models::{
+    date_time::{EventTime, TimeInterval},
+    day_of_week::{DayOfWeek, Days},
+    diff_model::{DayChange, DayDiff, EventsDiff, Reassignment},
+    educator_model::{ContingentUnitName, EducatorDay, EventLocation},
+};
 
 use super::*;
 
-fn generate_empty_educator_events_days() -> [EducatorDay; 6] {
+fn render_schedule_diff(diff: &ScheduleDiff) -> String {
+    Letters::default().render_schedule_diff(diff).unwrap()
+}
+
+fn collect_all_tracked_diffs(changed: &ChangedSchedules, user: &User) -> String {
+    Letters::default()
+        .collect_all_tracked_diffs(changed, user)
+        .unwrap()
+}
+
+fn collect_subscribed_schedules(schedule: &Schedule, subscriptions: &Subscriptions) -> String {
+    Letters::default()
+        .collect_subscribed_schedules(schedule, subscriptions)
+        .unwrap()
+}
+
+fn generate_empty_educator_events_days() -> Days {
     [
-        EducatorDay {
-            day_string: "Понедельник".to_owned(),
-            day_study_events_count: 0,
-            day_study_events: BTreeSet::new(),
-        },
-        EducatorDay {
-            day_string: "Вторник".to_owned(),
-            day_study_events_count: 0,
-            day_study_events: BTreeSet::new(),
-        },
-        EducatorDay {
-            day_string: "Среда".to_owned(),
-            day_study_events_count: 0,
-            day_study_events: BTreeSet::new(),
-        },
-        EducatorDay {
-            day_string: "Четверг".to_owned(),
-            day_study_events_count: 0,
-            day_study_events: BTreeSet::new(),
-        },
-        EducatorDay {
-            day_string: "Пятница".to_owned(),
-            day_study_events_count: 0,
-            day_study_events: BTreeSet::new(),
-        },
-        EducatorDay {
-            day_string: "Суббота".to_owned(),
-            day_study_events_count: 0,
-            day_study_events: BTreeSet::new(),
-        },
+        (
+            DayOfWeek::Monday,
+            EducatorDay {
+                day: None,
+                day_string: "Понедельник".to_owned(),
+                day_study_events_count: 0,
+                day_study_events: BTreeSet::new(),
+            },
+        ),
+        (
+            DayOfWeek::Tuesday,
+            EducatorDay {
+                day: None,
+                day_string: "Вторник".to_owned(),
+                day_study_events_count: 0,
+                day_study_events: BTreeSet::new(),
+            },
+        ),
+        (
+            DayOfWeek::Wednesday,
+            EducatorDay {
+                day: None,
+                day_string: "Среда".to_owned(),
+                day_study_events_count: 0,
+                day_study_events: BTreeSet::new(),
+            },
+        ),
+        (
+            DayOfWeek::Thursday,
+            EducatorDay {
+                day: None,
+                day_string: "Четверг".to_owned(),
+                day_study_events_count: 0,
+                day_study_events: BTreeSet::new(),
+            },
+        ),
+        (
+            DayOfWeek::Friday,
+            EducatorDay {
+                day: None,
+                day_string: "Пятница".to_owned(),
+                day_study_events_count: 0,
+                day_study_events: BTreeSet::new(),
+            },
+        ),
+        (
+            DayOfWeek::Saturday,
+            EducatorDay {
+                day: None,
+                day_string: "Суббота".to_owned(),
+                day_study_events_count: 0,
+                day_study_events: BTreeSet::new(),
+            },
+        ),
     ]
+    .into()
 }
 
 #[test]
@@ -45,6 +118,7 @@ fn get_users_valid_json() {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
 
     let users = get_users(&args).unwrap();
@@ -62,6 +136,7 @@ fn get_prev_events_correct_json() {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
 
     let prev_ev = get_previous_events(&args).unwrap();
@@ -69,63 +144,84 @@ fn get_prev_events_correct_json() {
     let mut prev_ev_ref = BTreeMap::new();
 
     let mut warhol_educator_events_days = generate_empty_educator_events_days();
-    warhol_educator_events_days[0] = EducatorDay {
-        day_string: "Понедельник".to_string(),
-        day_study_events_count: 1,
-        day_study_events: BTreeSet::from([DayStudyEvent {
-            time_interval_string: "08:30-10:00".to_string(),
-            start: "08:30".to_string(),
-            end: "10:00".to_string(),
-            subject: "Как превратить искусство в массовый продукт".to_string(),
-            dates: vec!["01.09.1963".to_string()],
-            event_locations: BTreeSet::from([EventLocation {
-                display_name: "231 East 47th Street".to_string(),
-            }]),
-            contingent_unit_names: BTreeSet::from([ContingentUnitName {
-                item1: "Группа".to_string(),
-                item2: "101A".to_string(),
+    warhol_educator_events_days.insert(
+        DayOfWeek::Monday,
+        EducatorDay {
+            day: None,
+            day_string: "Понедельник".to_string(),
+            day_study_events_count: 1,
+            day_study_events: BTreeSet::from([DayStudyEvent {
+                time_interval: "08:30-10:00".parse().unwrap(),
+                start: "08:30".parse().unwrap(),
+                end: "10:00".parse().unwrap(),
+                subject: "Как превратить искусство в массовый продукт".to_string(),
+                dates: BTreeSet::from([NaiveDate::from_ymd_opt(1963, 9, 1).unwrap()]),
+                event_locations: BTreeSet::from([EventLocation {
+                    display_name: "231 East 47th Street".to_string(),
+                }]),
+                contingent_unit_names: BTreeSet::from([ContingentUnitName {
+                    item1: "Группа".to_string(),
+                    item2: "101A".to_string(),
+                }]),
+                educators_display_text: String::new(),
+                is_cancelled: false,
+                time_was_changed: false,
+                locations_were_changed: false,
+                educators_were_reassigned: false,
             }]),
-        }]),
-    };
+        },
+    );
     let warhol = EducatorEvents {
         educator_long_display_text: "Энди Уорхол".to_string(),
         educator_master_id: 1928,
         educator_events_days: warhol_educator_events_days,
+        week_start: None,
     };
-    prev_ev_ref.insert(1928, warhol);
+    prev_ev_ref.insert((1928, None), warhol);
 
     let mut malevich_educator_events_days = generate_empty_educator_events_days();
-    malevich_educator_events_days[1] = EducatorDay {
-        day_string: "Вторник".to_string(),
-        day_study_events_count: 1,
-        day_study_events: BTreeSet::from([DayStudyEvent {
-            time_interval_string: "09:00-10:30".to_string(),
-            start: "09:00".to_string(),
-            end: "10:30".to_string(),
-            subject: "От кубизма к супрематизму".to_string(),
-            dates: vec!["29.12.1915".to_string()],
-            event_locations: BTreeSet::from([EventLocation {
-                display_name: "Дворцовая площадь, д. 6/8".to_string(),
+    malevich_educator_events_days.insert(
+        DayOfWeek::Tuesday,
+        EducatorDay {
+            day: None,
+            day_string: "Вторник".to_string(),
+            day_study_events_count: 1,
+            day_study_events: BTreeSet::from([DayStudyEvent {
+                time_interval: "09:00-10:30".parse().unwrap(),
+                start: "09:00".parse().unwrap(),
+                end: "10:30".parse().unwrap(),
+                subject: "От кубизма к супрематизму".to_string(),
+                dates: BTreeSet::from([NaiveDate::from_ymd_opt(1915, 12, 29).unwrap()]),
+                event_locations: BTreeSet::from([EventLocation {
+                    display_name: "Дворцовая площадь, д. 6/8".to_string(),
+                }]),
+                contingent_unit_names: BTreeSet::from([
+                    ContingentUnitName {
+                        item1: "Группа".to_string(),
+                        item2: "201A".to_string(),
+                    },
+                    ContingentUnitName {
+                        item1: "Группа".to_string(),
+                        item2: "201B".to_string(),
+                    },
+                ]),
+                educators_display_text: String::new(),
+                is_cancelled: false,
+                time_was_changed: false,
+                locations_were_changed: false,
+                educators_were_reassigned: false,
             }]),
-            contingent_unit_names: BTreeSet::from([
-                ContingentUnitName {
-                    item1: "Группа".to_string(),
-                    item2: "201A".to_string(),
-                },
-                ContingentUnitName {
-                    item1: "Группа".to_string(),
-                    item2: "201B".to_string(),
-                },
-            ]),
-        }]),
-    };
+        },
+    );
     let malevich = EducatorEvents {
         educator_long_display_text: "Казимир Малевич".to_string(),
         educator_master_id: 1879,
         educator_events_days: malevich_educator_events_days,
+        week_start: None,
     };
-    prev_ev_ref.insert(1879, malevich);
-    assert_eq!(prev_ev, prev_ev_ref);
+    prev_ev_ref.insert((1879, None), malevich);
+    assert_eq!(prev_ev.educators, prev_ev_ref);
+    assert!(prev_ev.groups.is_empty());
 }
 
 #[test]
@@ -134,30 +230,10 @@ fn get_prev_events_non_existent_json() {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("non_existent.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
-    let test_map = get_previous_events(&args).unwrap();
-    let ref_map = BTreeMap::new();
-    assert_eq!(test_map, ref_map)
-}
-
-#[test]
-fn format_event_as_string1() {
-    let test_event = DayStudyEvent {
-        time_interval_string: "09:30-11.00".to_string(),
-        start: "09:30".to_string(),
-        end: "11:00".to_string(),
-        subject: "Матлогика".to_string(),
-        dates: vec!["01.09.2025".to_string()],
-        event_locations: BTreeSet::from([EventLocation {
-            display_name: "Университетский пр. 28Д".to_string(),
-        }]),
-        contingent_unit_names: BTreeSet::from([ContingentUnitName {
-            item1: "Группа".to_string(),
-            item2: "23.Б15-мм".to_string(),
-        }]),
-    };
-    let formatted_event = format_event_as_string(&test_event);
-    assert_eq!(formatted_event, "    <b>Предмет:</b> Матлогика<br>    <b>Время:</b> 09:30-11.00<br>    <b>Даты:</b> 01.09.2025<br>    <b>Места:</b> Университетский пр. 28Д<br>    <b>Направления:</b> Группа 23.Б15-мм<br>")
+    let test_schedule = get_previous_events(&args).unwrap();
+    assert_eq!(test_schedule, Schedule::default())
 }
 
 #[test]
@@ -166,23 +242,25 @@ fn collect_all_tracked_diffs_multiple_diffs() {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("example.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
     let args_new = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("example.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.many_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
 
     let users = get_users(&args_new).unwrap();
     let old = get_previous_events(&args_old).unwrap();
     let new = get_previous_events(&args_new).unwrap();
-    let diff_test = generate_diff_messages(&old, &new);
+    let diff_test = generate_all_diffs(&old, &new, NaiveDate::MIN);
     let diff = collect_all_tracked_diffs(&diff_test, &users[0]);
     // method .iter() of HashSet takes educators in arbitrary order, which is no problem for resulting letter, but pain for testing
-    let malevich_first = "В расписании преподавателя <b>Казимир Малевич</b> произошли изменения:<br><br><b><font size=\"5\">Вторник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> 22.12.1915, 29.12.1915<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 201A, Группа 201B<br><br>    <b>Предмет:</b> Декларация прав художника<br>    <b>Время:</b> 11:00-12:30<br>    <b>Даты:</b> 15.08.1918, 22.08.1918<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 202A<br><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> 29.12.1915<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 201A, Группа 201B<br><br><br> <br>В расписании преподавателя <b>Энди Уорхол</b> произошли изменения:<br><br><b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963, 08.09.1963<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 101A, Группа 101B<br><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 10:15-11:45<br>    <b>Даты:</b> 01.09.1968, 08.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 102B<br><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 101A<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Среда:</font></b><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br><br>";
-    let warhol_first = "В расписании преподавателя <b>Энди Уорхол</b> произошли изменения:<br><br><b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963, 08.09.1963<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 101A, Группа 101B<br><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 10:15-11:45<br>    <b>Даты:</b> 01.09.1968, 08.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 102B<br><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 101A<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Среда:</font></b><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br><br><br> <br>В расписании преподавателя <b>Казимир Малевич</b> произошли изменения:<br><br><b><font size=\"5\">Вторник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> 22.12.1915, 29.12.1915<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 201A, Группа 201B<br><br>    <b>Предмет:</b> Декларация прав художника<br>    <b>Время:</b> 11:00-12:30<br>    <b>Даты:</b> 15.08.1918, 22.08.1918<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 202A<br><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> 29.12.1915<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 201A, Группа 201B<br><br>";
+    let malevich_first = "В расписании преподавателя <b>Казимир Малевич</b> произошли изменения:<br><br><b><font size=\"5\">Вторник:</font></b><br><em style=\"color:orange;\">Изменённые события:</em><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> добавлены 22.12.1915<br><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Декларация прав художника<br>    <b>Время:</b> 11:00-12:30<br>    <b>Даты:</b> 15.08.1918, 22.08.1918<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 202A<br><br><br> <br>В расписании преподавателя <b>Энди Уорхол</b> произошли изменения:<br><br><b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:orange;\">Изменённые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> добавлены 08.09.1963<br>    <b>Направления:</b> Группа 101A → Группа 101A, Группа 101B<br><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 10:15-11:45<br>    <b>Даты:</b> 01.09.1968, 08.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 102B<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Среда:</font></b><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br><br>";
+    let warhol_first = "В расписании преподавателя <b>Энди Уорхол</b> произошли изменения:<br><br><b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:orange;\">Изменённые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> добавлены 08.09.1963<br>    <b>Направления:</b> Группа 101A → Группа 101A, Группа 101B<br><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 10:15-11:45<br>    <b>Даты:</b> 01.09.1968, 08.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 102B<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Среда:</font></b><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br><br><br> <br>В расписании преподавателя <b>Казимир Малевич</b> произошли изменения:<br><br><b><font size=\"5\">Вторник:</font></b><br><em style=\"color:orange;\">Изменённые события:</em><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> добавлены 22.12.1915<br><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Декларация прав художника<br>    <b>Время:</b> 11:00-12:30<br>    <b>Даты:</b> 15.08.1918, 22.08.1918<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 202A<br><br>";
     let diff_valid_mixed_educators_order = diff == malevich_first || diff == warhol_first;
-    assert_eq!(diff_valid_mixed_educators_order, true)
+    assert!(diff_valid_mixed_educators_order)
 }
 
 #[test]
@@ -191,11 +269,12 @@ fn collect_all_tracked_diffs_no_diffs() {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("example.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
 
     let users = get_users(&args_old).unwrap();
     let old = get_previous_events(&args_old).unwrap();
-    let diff_test = generate_diff_messages(&old, &old);
+    let diff_test = generate_all_diffs(&old, &old, NaiveDate::MIN);
     let diff = collect_all_tracked_diffs(&diff_test, &users[0]);
     assert_eq!(diff, "")
 }
@@ -222,23 +301,35 @@ diff:   Новый день:
         Места: 33 Union Square West
         Направления: Группа 103C */
 #[test]
-fn generate_diff_messages_new_day() {
+fn generate_diffs_new_day() {
     let args_old = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
     let args_new = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.new_day.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
 
     let old = get_previous_events(&args_old).unwrap();
     let new = get_previous_events(&args_new).unwrap();
-    let diff = generate_diff_messages(&old, &new);
-    assert_eq!(diff.get(&1928).unwrap().1, "<em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Среда:</font></b><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br>");
-    assert_eq!(diff.get(&1879), None);
+    let diff = generate_diffs(&old.educators, &new.educators);
+    let wednesday = &new.educators[&(1928, None)].educator_events_days[&DayOfWeek::Wednesday];
+    let expected = DayDiff {
+        day_string: "Среда".to_string(),
+        change: DayChange::Added {
+            events: wednesday.day_study_events.iter().cloned().collect(),
+        },
+    };
+    assert_eq!(
+        diff[&(1928, None)].1.days,
+        BTreeMap::from([(DayOfWeek::Wednesday, expected)])
+    );
+    assert_eq!(diff.get(&(1879, None)), None);
 }
 
 /*
@@ -250,23 +341,25 @@ diff:   Понедельник:
         Места: 33 Union Square West
         Направления: Группа 103C */
 #[test]
-fn generate_diff_messages_old_day_new_event() {
+fn generate_diffs_old_day_new_event() {
     let args_old = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
     let args_new = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.old_day_new_event.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
 
     let old = get_previous_events(&args_old).unwrap();
     let new = get_previous_events(&args_new).unwrap();
-    let diff = generate_diff_messages(&old, &new);
-    assert_eq!(diff.get(&1928).unwrap().1, "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br>");
-    assert_eq!(diff.get(&1879), None);
+    let diff = generate_diffs(&old.educators, &new.educators);
+    assert_eq!(render_schedule_diff(&diff[&(1928, None)].1), "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br>");
+    assert_eq!(diff.get(&(1879, None)), None);
 }
 
 /*
@@ -285,51 +378,67 @@ diff:   Понедельник:
         Места: 231 East 47th Street
         Направления: Группа 101A*/
 #[test]
-fn generate_diff_messages_old_day_old_event_new_group() {
+fn generate_diffs_old_day_old_event_new_group() {
     let args_old = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
     let args_new = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.old_day_old_event_new_group.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
 
     let old = get_previous_events(&args_old).unwrap();
     let new = get_previous_events(&args_new).unwrap();
-    let diff = generate_diff_messages(&old, &new);
-    assert_eq!(diff.get(&1928).unwrap().1, "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 101A, Группа 101B<br><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 101A<br>");
-    assert_eq!(diff.get(&1879), None);
+    let diff = generate_diffs(&old.educators, &new.educators);
+    assert_eq!(render_schedule_diff(&diff[&(1928, None)].1), "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:orange;\">Изменённые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Направления:</b> Группа 101A → Группа 101A, Группа 101B<br>");
+    assert_eq!(diff.get(&(1879, None)), None);
 }
 
-/*
-diff:   Новый день:
-        Вторник:
-        Предмет: От кубизма к супрематизму
-        Время: 09:00-10:30
-        Даты: 29.12.1915
-        Места: Дворцовая площадь, д. 6/8
-        Направления: Группа 201A, Группа 201B */
 #[test]
-fn generate_diff_messages_new_educator() {
+fn generate_diffs_new_educator_is_a_baseline() {
     let args_old = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.only_warhol.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
     let args_new = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
 
     let old = get_previous_events(&args_old).unwrap();
     let new = get_previous_events(&args_new).unwrap();
-    let diff = generate_diff_messages(&old, &new);
-    assert_eq!(diff.get(&1928), None);
-    assert_eq!(diff.get(&1879).unwrap().1, "<em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Вторник:</font></b><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> 29.12.1915<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 201A, Группа 201B<br>");
+    let diff = generate_diffs(&old.educators, &new.educators);
+    assert!(diff.is_empty());
+
+    let mut users = get_users(&args_new).unwrap();
+    let email = users[0].email.clone();
+    let current = current_subscriptions(&users, &new);
+    assert_eq!(current[&email].educators, BTreeSet::from([1879, 1928]));
+    /* snapshots without subscriptions welcome no one */
+    assert!(new_subscriptions(None, &current).is_empty());
+
+    let previous = BTreeMap::from([(
+        email.clone(),
+        Subscriptions {
+            educators: BTreeSet::from([1928]),
+            ..Default::default()
+        },
+    )]);
+    let subscribed = new_subscriptions(Some(&previous), &current);
+    assert_eq!(subscribed[&email].educators, BTreeSet::from([1879]));
+    assert_eq!(collect_subscribed_schedules(&new, &subscribed[&email]), "Расписание преподавателя <b>Казимир Малевич</b>:<br><br><b><font size=\"5\">Вторник:</font></b><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> 29.12.1915<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 201A, Группа 201B<br><br>");
+
+    unwatch(&mut users[0], &subscribed[&email]);
+    assert_eq!(users[0].watch_educators, BTreeSet::from([1928]));
 }
 
 /*
@@ -360,23 +469,25 @@ diff:   Понедельник:
         Места: 33 Union Square West
         Направления: Группа 103C */
 #[test]
-fn generate_diff_messages_many_days() {
+fn generate_diffs_many_days() {
     let args_old = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
     let args_new = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.many_days.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
 
     let old = get_previous_events(&args_old).unwrap();
     let new = get_previous_events(&args_new).unwrap();
-    let diff = generate_diff_messages(&old, &new);
-    assert_eq!(diff.get(&1928).unwrap().1, "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963, 08.09.1963<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 101A, Группа 101B<br><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 10:15-11:45<br>    <b>Даты:</b> 01.09.1968, 08.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 102B<br><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 101A<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Вторник:</font></b><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> 22.12.1915, 29.12.1915<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 201A, Группа 201B<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Среда:</font></b><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br>");
-    assert_eq!(diff.get(&1879), None);
+    let diff = generate_diffs(&old.educators, &new.educators);
+    assert_eq!(render_schedule_diff(&diff[&(1928, None)].1), "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:orange;\">Изменённые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> добавлены 08.09.1963<br>    <b>Направления:</b> Группа 101A → Группа 101A, Группа 101B<br><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 10:15-11:45<br>    <b>Даты:</b> 01.09.1968, 08.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 102B<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Вторник:</font></b><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> 22.12.1915, 29.12.1915<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 201A, Группа 201B<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Среда:</font></b><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br>");
+    assert_eq!(diff.get(&(1879, None)), None);
 }
 
 /*
@@ -388,21 +499,1737 @@ diff:   Среда:
         Места: 33 Union Square West
         Направления: Группа 103C */
 #[test]
-fn generate_diff_messages_delete_last_event_of_the_day() {
+fn generate_diffs_delete_last_event_of_the_day() {
     let args_old = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.new_day.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
     let args_new = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("tests/test.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
 
     let old = get_previous_events(&args_old).unwrap();
     let new = get_previous_events(&args_new).unwrap();
-    let diff = generate_diff_messages(&old, &new);
-    assert_eq!(diff.get(&1928).unwrap().1, "<b><font size=\"5\">Среда:</font></b><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br>");
-    assert_eq!(diff.get(&1879), None);
+    let diff = generate_diffs(&old.educators, &new.educators);
+    let wednesday = &old.educators[&(1928, None)].educator_events_days[&DayOfWeek::Wednesday];
+    let expected = DayDiff {
+        day_string: "Среда".to_string(),
+        change: DayChange::Modified(EventsDiff {
+            removed: wednesday.day_study_events.iter().cloned().collect(),
+            ..Default::default()
+        }),
+    };
+    assert_eq!(
+        diff[&(1928, None)].1.days,
+        BTreeMap::from([(DayOfWeek::Wednesday, expected)])
+    );
+    assert_eq!(diff.get(&(1879, None)), None);
+}
+
+#[test]
+fn get_prev_events_groups_json() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.group.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+
+    let prev_ev = get_previous_events(&args).unwrap();
+    assert!(prev_ev.educators.is_empty());
+    let group = prev_ev
+        .groups
+        .get(&(394847, NaiveDate::from_ymd_opt(2024, 10, 14)))
+        .unwrap();
+    assert_eq!(group.student_group_display_name, "Группа 23.Б15-мм");
+    assert_eq!(group.days.len(), 1);
+    let event = group.days[&DayOfWeek::Monday]
+        .day_study_events
+        .first()
+        .unwrap();
+    assert_eq!(event.educators_display_text, "Энди Уорхол");
+    assert!(event.dates.is_empty());
+    assert!(event.contingent_unit_names.is_empty());
+}
+
+/*
+diff:   понедельник, 14 октября:
+        Новые события:
+        Предмет: Матлогика, практика
+        ...
+        Новый день:
+        среда, 16 октября:
+        Предмет: Теория графов, лекция
+        ... */
+#[test]
+fn generate_diffs_group_changes() {
+    let args_old = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.group.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let args_new = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.group_changes.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+
+    let old = get_previous_events(&args_old).unwrap();
+    let new = get_previous_events(&args_new).unwrap();
+    let diff = generate_diffs(&old.groups, &new.groups);
+    assert_eq!(render_schedule_diff(&diff[&(394847, NaiveDate::from_ymd_opt(2024, 10, 14))].1), "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Матлогика, практика<br>    <b>Время:</b> 11:15–12:50<br>    <b>Места:</b> Университетский пр. 28Д<br>    <b>Преподаватели:</b> Казимир Малевич<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Среда:</font></b><br>    <b>Предмет:</b> Теория графов, лекция<br>    <b>Время:</b> 13:40–15:15<br>    <b>Места:</b> Университетский пр. 28Д<br>    <b>Преподаватели:</b> Энди Уорхол<br>");
+
+    /* days missing in the new schedule are reported as removed events */
+    let diff = generate_diffs(&new.groups, &old.groups);
+    assert_eq!(render_schedule_diff(&diff[&(394847, NaiveDate::from_ymd_opt(2024, 10, 14))].1), "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> Матлогика, практика<br>    <b>Время:</b> 11:15–12:50<br>    <b>Места:</b> Университетский пр. 28Д<br>    <b>Преподаватели:</b> Казимир Малевич<br><br><b><font size=\"5\">Среда:</font></b><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> Теория графов, лекция<br>    <b>Время:</b> 13:40–15:15<br>    <b>Места:</b> Университетский пр. 28Д<br>    <b>Преподаватели:</b> Энди Уорхол<br>");
+}
+
+#[test]
+fn generate_diffs_group_other_week() {
+    let args_old = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.group_changes.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let args_new = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.group_next_week.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+
+    let old = get_previous_events(&args_old).unwrap();
+    let new = get_previous_events(&args_new).unwrap();
+    let diff = generate_diffs(&old.groups, &new.groups);
+    assert!(diff.is_empty());
+}
+
+#[test]
+fn collect_all_tracked_diffs_groups() {
+    let args_old = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.group.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let args_new = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.group_changes.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+
+    let mut users = get_users(&args_new).unwrap();
+    let old = get_previous_events(&args_old).unwrap();
+    let new = get_previous_events(&args_new).unwrap();
+    let diff_test = generate_all_diffs(&old, &new, NaiveDate::MIN);
+    assert_eq!(collect_all_tracked_diffs(&diff_test, &users[0]), "");
+
+    users[0].watch_groups.insert(394847);
+    let diff = collect_all_tracked_diffs(&diff_test, &users[0]);
+    assert!(diff.starts_with(
+        "В расписании группы <b>Группа 23.Б15-мм</b> на неделе 14.10–20.10.2024 произошли изменения:<br><br><b><font size=\"5\">Понедельник, 14.10:</font></b>"
+    ));
+}
+
+#[test]
+fn watched_weeks_start_on_monday() {
+    let thursday = NaiveDate::from_ymd_opt(2024, 10, 17).unwrap();
+    let monday = NaiveDate::from_ymd_opt(2024, 10, 14).unwrap();
+    assert_eq!(week_monday(thursday), monday);
+    assert_eq!(week_monday(monday), monday);
+    assert_eq!(watched_weeks(thursday, 0), vec![monday]);
+    assert_eq!(
+        watched_weeks(thursday, 2),
+        vec![
+            monday,
+            NaiveDate::from_ymd_opt(2024, 10, 21).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 10, 28).unwrap()
+        ]
+    );
+}
+
+#[test]
+fn generate_diffs_new_week_of_tracked_educator() {
+    let args_old = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let args_new = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.many_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let monday = NaiveDate::from_ymd_opt(2024, 10, 14);
+    let date_events = |events: BTreeMap<ScheduleKey, EducatorEvents>| {
+        events
+            .into_values()
+            .map(|mut educator| {
+                educator.week_start = monday;
+                (educator.key(), educator)
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+
+    let undated_old = get_previous_events(&args_old).unwrap().educators;
+    let new = date_events(get_previous_events(&args_new).unwrap().educators);
+    /* snapshots without a week are never compared with dated ones */
+    let diff = generate_diffs(&undated_old, &new);
+    assert!(diff.is_empty());
+
+    let old = date_events(undated_old);
+    let changed = ChangedSchedules {
+        educators: generate_diffs(&old, &new),
+        ..Default::default()
+    };
+    assert!(changed.educators.contains_key(&(1928, monday)));
+    let users = get_users(&args_old).unwrap();
+    let diff = collect_all_tracked_diffs(&changed, &users[0]);
+    assert!(diff.starts_with(
+        "В расписании преподавателя <b>Казимир Малевич</b> на неделе 14.10–20.10.2024 произошли изменения:"
+    ));
+}
+
+fn get_test_config() -> Config {
+    serde_json::from_reader(File::open("tests/test.config.json").unwrap()).unwrap()
+}
+
+#[test]
+fn carry_forward_failed_keeps_old_events() {
+    let args_old = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let old = get_previous_events(&args_old).unwrap();
+    let mut new = Schedule::default();
+    let failures = vec![
+        FetchFailure {
+            schedule: ScheduleRef::Educator((1928, None)),
+            error: "HTTP status server error (500 Internal Server Error)".to_string(),
+        },
+        FetchFailure {
+            schedule: ScheduleRef::Group((394847, None)),
+            error: "error decoding response body".to_string(),
+        },
+    ];
+    carry_forward_failed(&old, &mut new, &failures);
+
+    assert_eq!(
+        new.educators.get(&(1928, None)),
+        old.educators.get(&(1928, None))
+    );
+    assert_eq!(new.educators.len(), 1);
+    assert!(new.groups.is_empty());
+    /* carried forward educator doesn't look changed */
+    assert!(generate_diffs(&old.educators, &new.educators).is_empty());
+    assert_eq!(
+        failures[0].to_string(),
+        "educator 1928: HTTP status server error (500 Internal Server Error)"
+    );
+}
+
+#[tokio::test]
+async fn with_retries_gives_up_after_all_attempts() {
+    let mut config = get_test_config();
+    config.fetch_retries = 2;
+    config.fetch_retry_delay_ms = 1;
+    /* nothing listens on a port of a dropped listener, so every request fails */
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let http_client = Client::new();
+    let rate_limiter = RateLimiter::new(0.0);
+    let url = format!("http://127.0.0.1:{}", port);
+    let attempts = AtomicU32::new(0);
+
+    let result = with_retries(&config, || {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        send_request(&http_client, &rate_limiter, &url)
+    })
+    .await;
+
+    assert!(result.is_err());
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+}
+
+/// Answers each incoming connection with the next of `responses`.
+/// Returns server's url and a handle resolving to received requests
+async fn serve_responses(responses: Vec<String>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 4096];
+            let read = socket.read(&mut request).await.unwrap();
+            requests.push(String::from_utf8_lossy(&request[..read]).to_string());
+            socket.write_all(response.as_bytes()).await.unwrap();
+            socket.shutdown().await.unwrap();
+        }
+        requests
+    });
+    (url, server)
+}
+
+fn http_response(status: &str, headers: &[&str], body: &str) -> String {
+    let mut response = format!("HTTP/1.1 {}\r\nConnection: close\r\n", status);
+    for header in headers {
+        response.push_str(&format!("{}\r\n", header));
+    }
+    response.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+    response
+}
+
+#[tokio::test]
+async fn with_retries_waits_for_retry_after() {
+    let mut config = get_test_config();
+    config.fetch_retries = 1;
+    config.fetch_retry_delay_ms = 1;
+    let (url, _) = serve_responses(vec![
+        http_response("429 Too Many Requests", &["Retry-After: 1"], ""),
+        http_response("200 OK", &[], "ok"),
+    ])
+    .await;
+    let http_client = Client::new();
+    let rate_limiter = RateLimiter::new(0.0);
+
+    let start = Instant::now();
+    let response = with_retries(&config, || send_request(&http_client, &rate_limiter, &url))
+        .await
+        .unwrap();
+
+    assert_eq!(response.text().await.unwrap(), "ok");
+    assert!(start.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn too_long_retry_after_does_not_pause_other_requests() {
+    let mut config = get_test_config();
+    config.fetch_retries = 3;
+    config.fetch_retry_delay_ms = 1;
+    let (url, _) = serve_responses(vec![
+        http_response("503 Service Unavailable", &["Retry-After: 86400"], ""),
+        http_response("200 OK", &[], "ok"),
+    ])
+    .await;
+    let http_client = Client::new();
+    let rate_limiter = RateLimiter::new(0.0);
+    let attempts = AtomicU32::new(0);
+
+    let result = with_retries(&config, || {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        send_request(&http_client, &rate_limiter, &url)
+    })
+    .await;
+    assert!(matches!(result, Err(FetchError::Throttled(_, Some(_)))));
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+    /* the request was given up, so the next one isn't held back for a day */
+    let start = Instant::now();
+    let response = send_request(&http_client, &rate_limiter, &url)
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "ok");
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn with_retries_does_not_repeat_client_errors() {
+    let mut config = get_test_config();
+    config.fetch_retries = 3;
+    config.fetch_retry_delay_ms = 1;
+    let (url, _) = serve_responses(vec![http_response("404 Not Found", &[], "")]).await;
+    let http_client = Client::new();
+    let rate_limiter = RateLimiter::new(0.0);
+    let attempts = AtomicU32::new(0);
+
+    let result = with_retries(&config, || {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        send_request(&http_client, &rate_limiter, &url)
+    })
+    .await;
+
+    assert!(matches!(result, Err(FetchError::Http(_))));
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn parse_retry_after_seconds_and_dates() {
+    let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+        .unwrap()
+        .with_timezone(&Utc);
+    assert_eq!(
+        parse_retry_after("120", now),
+        Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+        parse_retry_after("Wed, 21 Oct 2015 07:30:00 GMT", now),
+        Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+        parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(parse_retry_after("soon", now), None);
+}
+
+#[tokio::test]
+async fn rate_limiter_spaces_requests() {
+    let rate_limiter = RateLimiter::new(50.0);
+    let start = Instant::now();
+    for _ in 0..5 {
+        rate_limiter.wait().await;
+    }
+    /* first request goes immediately, others wait 20ms each */
+    assert!(start.elapsed() >= Duration::from_millis(80));
+
+    rate_limiter.pause_for(Duration::from_millis(100)).await;
+    let paused = Instant::now();
+    rate_limiter.wait().await;
+    assert!(paused.elapsed() >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn get_educator_events_by_id_uses_configured_api() {
+    let educator = r#"{
+        "EducatorLongDisplayText": "Энди Уорхол",
+        "EducatorMasterId": 1928,
+        "EducatorEventsDays": [
+            {"DayString": "Понедельник", "DayStudyEventsCount": 0, "DayStudyEvents": []},
+            {"DayString": "Вторник", "DayStudyEventsCount": 0, "DayStudyEvents": []},
+            {"DayString": "Среда", "DayStudyEventsCount": 0, "DayStudyEvents": []},
+            {"DayString": "Четверг", "DayStudyEventsCount": 0, "DayStudyEvents": []},
+            {"DayString": "Пятница", "DayStudyEventsCount": 0, "DayStudyEvents": []},
+            {"DayString": "Суббота", "DayStudyEventsCount": 0, "DayStudyEvents": []}
+        ]
+    }"#;
+    let (url, server) = serve_responses(vec![http_response(
+        "200 OK",
+        &["Content-Type: application/json"],
+        educator,
+    )])
+    .await;
+    let settings = HttpSettings {
+        user_agent: "geraltt-test".to_string(),
+        extra_headers: BTreeMap::from([("X-Mirror-Token".to_string(), "secret".to_string())]),
+        ..Default::default()
+    };
+    let http_client = build_http_client(&settings).unwrap();
+    let rate_limiter = RateLimiter::new(0.0);
+    let monday = NaiveDate::from_ymd_opt(2024, 10, 14).unwrap();
+
+    let (key, events) = get_educator_events_by_id(
+        &http_client,
+        &rate_limiter,
+        &SchemaChecker::new(false),
+        &format!("{}/api/v1/", url),
+        1928,
+        monday,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(key, (1928, Some(monday)));
+    assert_eq!(events.educator_long_display_text, "Энди Уорхол");
+    let requests = server.await.unwrap();
+    let request = requests[0].to_lowercase();
+    assert!(request.starts_with("get /api/v1/educators/1928/events/2024-10-14/2024-10-21 http/1.1"));
+    assert!(request.contains("user-agent: geraltt-test"));
+    assert!(request.contains("x-mirror-token: secret"));
+}
+
+#[test]
+fn build_http_client_rejects_bad_settings() {
+    let settings = HttpSettings {
+        extra_headers: BTreeMap::from([("Bad Header".to_string(), "value".to_string())]),
+        ..Default::default()
+    };
+    assert!(build_http_client(&settings).is_err());
+
+    let settings = HttpSettings {
+        proxy: Some("not a url".to_string()),
+        ..Default::default()
+    };
+    assert!(build_http_client(&settings).is_err());
+}
+
+fn found_educator(id: u32, full_name: &str) -> FoundEducator {
+    FoundEducator {
+        id,
+        display_name: full_name.to_string(),
+        full_name: full_name.to_string(),
+    }
+}
+
+#[test]
+fn choose_educator_by_name() {
+    let warhol = found_educator(1928, "Уорхол Энди");
+    let warhol_junior = found_educator(1929, "Уорхол Энди Младший");
+    let malevich = found_educator(1879, "Малевич Казимир Северинович");
+
+    assert_eq!(
+        choose_educator("Малевич", std::slice::from_ref(&malevich)),
+        Ok(&malevich)
+    );
+    assert_eq!(
+        choose_educator("Кандинский", &[]),
+        Err("no educators found by name \"Кандинский\"".to_string())
+    );
+    let candidates = [warhol.clone(), warhol_junior];
+    assert_eq!(
+        choose_educator("Уорхол", &candidates),
+        Err("name \"Уорхол\" is ambiguous, put one of these ids into watch_educators or specify full name: 1928 (Уорхол Энди), 1929 (Уорхол Энди Младший)".to_string())
+    );
+    assert_eq!(choose_educator("уорхол  энди", &candidates), Ok(&warhol));
+}
+
+#[tokio::test]
+async fn search_educators_encodes_name() {
+    let (url, server) = serve_responses(vec![http_response(
+        "200 OK",
+        &["Content-Type: application/json"],
+        r#"{"Educators": [{"Id": 1879, "DisplayName": "Малевич К. С.", "FullName": "Малевич Казимир Северинович", "Employments": []}]}"#,
+    )])
+    .await;
+    let http_client = Client::new();
+    let rate_limiter = RateLimiter::new(0.0);
+
+    let found = search_educators(&http_client, &rate_limiter, &url, " Малевич К/С ")
+        .await
+        .unwrap();
+
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, 1879);
+    assert_eq!(found[0].full_name, "Малевич Казимир Северинович");
+    let requests = server.await.unwrap();
+    assert!(requests[0].starts_with(
+        "GET /educators/search/%D0%9C%D0%B0%D0%BB%D0%B5%D0%B2%D0%B8%D1%87%20%D0%9A%2F%D0%A1 HTTP/1.1"
+    ));
+}
+
+/// Answers name searches from a fixed list of educators
+struct NameSearchGetter {
+    educators: Vec<FoundEducator>,
+    searches: AtomicU32,
+}
+
+impl ScheduleGetter for NameSearchGetter {
+    async fn get_schedule(
+        &self,
+        _users: &[User],
+        _config: &Config,
+        _now: DateTime<Utc>,
+    ) -> (Schedule, FetchReport) {
+        (Schedule::default(), FetchReport::default())
+    }
+
+    async fn search_educators(
+        &self,
+        name: &str,
+        _config: &Config,
+    ) -> Result<Vec<FoundEducator>, FetchError> {
+        self.searches.fetch_add(1, Ordering::SeqCst);
+        Ok(self
+            .educators
+            .iter()
+            .filter(|educator| educator.full_name.starts_with(name))
+            .cloned()
+            .collect())
+    }
+}
+
+#[tokio::test]
+async fn resolve_educator_names_uses_cache() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let mut users = get_users(&args).unwrap();
+    users[0].watch_educator_names = BTreeSet::from([
+        "Малевич".to_string(),
+        "Уорхол".to_string(),
+        "Кандинский".to_string(),
+        "Шагал".to_string(),
+    ]);
+    let getter = NameSearchGetter {
+        educators: vec![
+            found_educator(1879, "Малевич Казимир Северинович"),
+            found_educator(1928, "Уорхол Энди"),
+            found_educator(1929, "Уорхол Энди Младший"),
+        ],
+        searches: AtomicU32::new(0),
+    };
+    let cache = BTreeMap::from([
+        ("Шагал".to_string(), 1887),
+        ("Кандинский".to_string(), 1866),
+        ("Родченко".to_string(), 1891),
+    ]);
+
+    let resolved = resolve_educator_names(&getter, &get_test_config(), &mut users, &cache).await;
+
+    /* ambiguous "Уорхол" is skipped, unused "Родченко" is dropped from cache */
+    assert_eq!(
+        resolved,
+        BTreeMap::from([
+            ("Кандинский".to_string(), 1866),
+            ("Малевич".to_string(), 1879),
+            ("Шагал".to_string(), 1887),
+        ])
+    );
+    assert_eq!(getter.searches.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        users[0].watch_educators,
+        BTreeSet::from([1866, 1879, 1887, 1928])
+    );
+}
+
+/// Returns no events along with a fixed schema drift
+struct DriftGetter {
+    issue: SchemaIssue,
+}
+
+impl ScheduleGetter for DriftGetter {
+    async fn get_schedule(
+        &self,
+        _users: &[User],
+        _config: &Config,
+        _now: DateTime<Utc>,
+    ) -> (Schedule, FetchReport) {
+        let schema_drift = SchemaReport {
+            endpoints: BTreeMap::from([(
+                "educators/{id}/events".to_string(),
+                BTreeSet::from([self.issue.clone()]),
+            )]),
+        };
+        let report = FetchReport {
+            failures: Vec::new(),
+            schema_drift,
+        };
+        (Schedule::default(), report)
+    }
+
+    async fn search_educators(
+        &self,
+        _name: &str,
+        _config: &Config,
+    ) -> Result<Vec<FoundEducator>, FetchError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Counts schema reports and sends nothing
+struct ReportCounter<'a> {
+    reports: &'a AtomicU32,
+}
+
+impl LetterSender for ReportCounter<'_> {
+    fn form_and_send_letters(
+        self,
+        _config: Config,
+        _letters: &Letters,
+        _changes: Vec<(User, ChangedSchedules)>,
+    ) {
+    }
+
+    fn send_schema_report(&self, _config: &Config, _report: &SchemaReport) {
+        self.reports.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn send_welcome_letter(&self, _config: &Config, _user: &User, _letter: &Letter) {}
+}
+
+#[tokio::test]
+async fn schema_drift_is_mailed_once() {
+    let report_path = std::env::temp_dir().join("tt_diff_schema_drift_is_mailed_once.json");
+    let _ = std::fs::remove_file(&report_path);
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: report_path.clone(),
+    };
+    let unknown = SchemaIssue {
+        kind: SchemaIssueKind::Unknown,
+        path: "EducatorEventsDays[].DayStudyEvents[].Room".to_string(),
+        detail: String::new(),
+    };
+    let retyped = SchemaIssue {
+        kind: SchemaIssueKind::Retyped,
+        path: "EducatorEventsDays[].DayStudyEvents[].Subject".to_string(),
+        detail: "invalid type: integer `1`, expected a string".to_string(),
+    };
+    let reports = AtomicU32::new(0);
+    let now = Utc::now();
+
+    for issue in [&unknown, &unknown, &retyped] {
+        let getter = DriftGetter {
+            issue: issue.clone(),
+        };
+        let sender = ReportCounter { reports: &reports };
+        run(getter, sender, &args, get_test_config(), now).await;
+    }
+    let _ = std::fs::remove_file(&report_path);
+
+    /* the same response doesn't mail the admin again, a different issue does */
+    assert_eq!(reports.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn location_events_are_fetched_and_diffed() {
+    let location = r#"{
+        "DisplayName1": "Университетский пр. 28Д, 2405",
+        "Days": [
+            {
+                "DayString": "понедельник, 14 октября",
+                "DayStudyEvents": [
+                    {
+                        "Start": "2024-10-14T09:30:00",
+                        "End": "2024-10-14T11:05:00",
+                        "Subject": "Матлогика, лекция",
+                        "TimeIntervalString": "09:30–11:05",
+                        "EducatorsDisplayText": "Энди Уорхол"
+                    }
+                ]
+            }
+        ]
+    }"#;
+    let (url, server) = serve_responses(vec![http_response(
+        "200 OK",
+        &["Content-Type: application/json"],
+        location,
+    )])
+    .await;
+    let http_client = build_http_client(&HttpSettings::default()).unwrap();
+    let rate_limiter = RateLimiter::new(0.0);
+    let monday = NaiveDate::from_ymd_opt(2024, 10, 14).unwrap();
+    let oid = "6a0d4c1e-2405";
+
+    let (key, new_events) = get_location_events_by_id(
+        &http_client,
+        &rate_limiter,
+        &SchemaChecker::new(false),
+        &url,
+        oid,
+        monday,
+    )
+    .await
+    .unwrap();
+    assert_eq!(key, (oid.to_string(), Some(monday)));
+    let requests = server.await.unwrap();
+    assert!(requests[0]
+        .starts_with("GET /classrooms/6a0d4c1e-2405/events/2024-10-14/2024-10-21 HTTP/1.1"));
+
+    let mut old_events = new_events.clone();
+    old_events.days.clear();
+    let old = Schedule {
+        locations: BTreeMap::from([(key.clone(), old_events)]),
+        ..Default::default()
+    };
+    let new = Schedule {
+        locations: BTreeMap::from([(key, new_events)]),
+        ..Default::default()
+    };
+    let changed = generate_all_diffs(&old, &new, NaiveDate::MIN);
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.location.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let mut users = get_users(&args).unwrap();
+    assert_eq!(collect_all_tracked_diffs(&changed, &users[0]), "");
+
+    users[0].watch_locations.insert(oid.to_string());
+    assert_eq!(collect_all_tracked_diffs(&changed, &users[0]), "В расписании аудитории <b>Университетский пр. 28Д, 2405</b> на неделе 14.10–20.10.2024 произошли изменения:<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Понедельник, 14.10:</font></b><br>    <b>Предмет:</b> Матлогика, лекция<br>    <b>Время:</b> 09:30–11:05<br>    <b>Преподаватели:</b> Энди Уорхол<br><br>");
+}
+
+#[test]
+fn event_dates_and_times_are_typed() {
+    let wire = r#"{
+        "Start": "2024-10-14T09:30:00",
+        "End": "2024-10-14T11:05:00",
+        "Subject": "Матлогика, лекция",
+        "TimeIntervalString": "09:30–11:05",
+        "Dates": ["14.10.2024", "21.10.2024"],
+        "EventLocations": [],
+        "ContingentUnitNames": [],
+        "IsCancelled": false,
+        "TimeWasChanged": false,
+        "LocationsWereChanged": false,
+        "EducatorsWereReassigned": false
+    }"#;
+    let event: DayStudyEvent = serde_json::from_str(wire).unwrap();
+    assert_eq!(event.start.date(), NaiveDate::from_ymd_opt(2024, 10, 14));
+    assert_eq!(event.start.time(), event.time_interval.start);
+    assert_eq!(event.end.time(), event.time_interval.end);
+    assert_eq!(
+        event.dates.last(),
+        NaiveDate::from_ymd_opt(2024, 10, 21).as_ref()
+    );
+    /* written back exactly as the API sent it */
+    let written = serde_json::to_value(&event).unwrap();
+    assert_eq!(
+        written,
+        serde_json::from_str::<serde_json::Value>(wire).unwrap()
+    );
+
+    /* formatting churn doesn't make events different */
+    let interval: TimeInterval = "8:30-10:00".parse().unwrap();
+    assert_eq!(interval, "08:30–10:00".parse().unwrap());
+    assert_eq!(interval.to_string(), "08:30–10:00");
+    assert_eq!(
+        "08:30:00".parse::<EventTime>().unwrap(),
+        "08:30".parse().unwrap()
+    );
+
+    let malformed = wire.replace("21.10.2024", "2024-10-21");
+    let error = serde_json::from_str::<DayStudyEvent>(&malformed).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("invalid date \"2024-10-21\", expected DD.MM.YYYY"));
+    let malformed = wire.replace("09:30–11:05", "09:30–11.05");
+    let error = serde_json::from_str::<DayStudyEvent>(&malformed).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("invalid time interval \"09:30–11.05\", expected HH:MM–HH:MM"));
+    assert!("25:00".parse::<EventTime>().is_err());
+}
+
+#[test]
+fn event_times_are_written_back_as_the_api_sent_them() {
+    /* intervals with a hyphen and times with seconds, unlike those of `event_dates_and_times_are_typed` */
+    let wire = r#"{"Start":"08:30:00","End":"10:00:00","Subject":"Как превратить искусство в массовый продукт","TimeIntervalString":"08:30-10:00","Dates":["01.09.1963"],"EventLocations":[{"DisplayName":"231 East 47th Street"}],"ContingentUnitNames":[{"Item1":"Группа","Item2":"101A"}],"IsCancelled":false,"TimeWasChanged":false,"LocationsWereChanged":false,"EducatorsWereReassigned":false}"#;
+    let event: DayStudyEvent = serde_json::from_str(wire).unwrap();
+    assert_eq!(serde_json::to_string(&event).unwrap(), wire);
+
+    for fixture in ["tests/test.less_events.json", "tests/test.group.json"] {
+        let raw: serde_json::Value = serde_json::from_reader(File::open(fixture).unwrap()).unwrap();
+        /* either a bare list of educators or lists of every kind of schedules */
+        let schedules = match raw.as_array() {
+            Some(educators) => educators.iter().collect::<Vec<_>>(),
+            None => raw
+                .as_object()
+                .unwrap()
+                .values()
+                .flat_map(|schedules| schedules.as_array().unwrap())
+                .collect(),
+        };
+        let events = schedules
+            .into_iter()
+            .flat_map(|schedule| {
+                schedule["EducatorEventsDays"]
+                    .as_array()
+                    .or(schedule["Days"].as_array())
+                    .unwrap()
+            })
+            .flat_map(|day| day["DayStudyEvents"].as_array().unwrap())
+            .collect::<Vec<_>>();
+        assert!(!events.is_empty());
+        for event in events {
+            let written = serde_json::to_value(DayStudyEvent::deserialize(event).unwrap()).unwrap();
+            for field in ["Start", "End", "TimeIntervalString"] {
+                assert_eq!(written[field], event[field]);
+            }
+        }
+    }
+}
+
+#[test]
+fn generate_diffs_flagged_changes() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.group.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let old = get_previous_events(&args).unwrap().groups;
+    let key = (394847, NaiveDate::from_ymd_opt(2024, 10, 14));
+    let change_event = |change: fn(&mut DayStudyEvent)| {
+        let mut new = old.clone();
+        let day = new
+            .get_mut(&key)
+            .unwrap()
+            .days
+            .get_mut(&DayOfWeek::Monday)
+            .unwrap();
+        let mut event = day.day_study_events.pop_first().unwrap();
+        change(&mut event);
+        day.day_study_events.insert(event);
+        new
+    };
+
+    let moved = change_event(|event| {
+        event.event_locations = BTreeSet::from([EventLocation {
+            display_name: "Университетский пр. 28Д, 1506".to_string(),
+        }]);
+        event.locations_were_changed = true;
+    });
+    let diff = generate_diffs(&old, &moved);
+    assert_eq!(render_schedule_diff(&diff[&key].1), "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:orange;\">Аудитория изменена учебным отделом:</em><br>    <b>Предмет:</b> Матлогика, лекция<br>    <b>Время:</b> 09:30–11:05<br>    <b>Места:</b> Университетский пр. 28Д → Университетский пр. 28Д, 1506<br>");
+
+    let cancelled = change_event(|event| event.is_cancelled = true);
+    let diff = generate_diffs(&old, &cancelled);
+    assert_eq!(render_schedule_diff(&diff[&key].1), "<b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:red;\">Отменено:</em><br>    <b>Предмет:</b> Матлогика, лекция<br>    <b>Время:</b> 09:30–11:05<br>    <b>Места:</b> Университетский пр. 28Д<br>    <b>Преподаватели:</b> Энди Уорхол<br>");
+
+    /* changes the flags don't explain are still reported as removed and added events */
+    let renamed = change_event(|event| {
+        event.subject = "Матлогика, практика".to_string();
+        event.locations_were_changed = true;
+    });
+    let diff = generate_diffs(&old, &renamed);
+    let DayChange::Modified(events) = &diff[&key].1.days[&DayOfWeek::Monday].change else {
+        panic!("Monday should be modified");
+    };
+    assert_eq!(events.added.len(), 1);
+    assert_eq!(events.removed.len(), 1);
+    assert!(events.modified.is_empty());
+}
+
+#[test]
+fn days_are_keyed_by_weekday() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    /* files with a list of six days are read as before and written as a map */
+    let old = get_previous_events(&args).unwrap().educators;
+    let warhol = old.get(&(1928, None)).unwrap();
+    assert_eq!(warhol.educator_events_days.len(), 6);
+    let written = serde_json::to_value(warhol).unwrap();
+    assert_eq!(
+        written["EducatorEventsDays"]["Monday"]["DayString"],
+        "Понедельник"
+    );
+    let read_back: EducatorEvents = serde_json::from_value(written).unwrap();
+    assert_eq!(&read_back, warhol);
+
+    /* a Sunday event doesn't break parsing and is reported as a new day */
+    let mut days = serde_json::to_value(&warhol.educator_events_days)
+        .unwrap()
+        .as_object()
+        .unwrap()
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    days.push(serde_json::json!({
+        "Day": "1963-09-08T00:00:00",
+        "DayString": "воскресенье, 8 сентября",
+        "DayStudyEvents": [{
+            "Start": "1963-09-08T12:00:00",
+            "End": "1963-09-08T13:30:00",
+            "Subject": "Воскресный показ",
+            "TimeIntervalString": "12:00–13:30",
+            "EventLocations": []
+        }]
+    }));
+    let mut new_warhol = serde_json::to_value(warhol).unwrap();
+    new_warhol["EducatorEventsDays"] = serde_json::Value::Array(days);
+    let new_warhol: EducatorEvents = serde_json::from_value(new_warhol).unwrap();
+    assert_eq!(new_warhol.educator_events_days.len(), 7);
+    let new = BTreeMap::from([((1928, None), new_warhol)]);
+    let diff = generate_diffs(&old, &new);
+    assert_eq!(render_schedule_diff(&diff[&(1928, None)].1), "<em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Воскресенье:</font></b><br>    <b>Предмет:</b> Воскресный показ<br>    <b>Время:</b> 12:00–13:30<br>");
+
+    /* and its removal is reported too */
+    let diff = generate_diffs(&new, &old);
+    let sunday = &diff[&(1928, None)].1.days[&DayOfWeek::Sunday];
+    assert_eq!(sunday.day_string, "воскресенье, 8 сентября");
+    assert!(matches!(&sunday.change, DayChange::Removed { events } if events.len() == 1));
+
+    /* days past Sunday can't be placed anywhere */
+    let unnamed_day = serde_json::json!({"DayString": "?", "DayStudyEvents": []});
+    let error = serde_json::from_value::<EducatorEvents>(serde_json::json!({
+        "EducatorLongDisplayText": "Энди Уорхол",
+        "EducatorMasterId": 1928,
+        "EducatorEventsDays": vec![unnamed_day; 8]
+    }))
+    .unwrap_err();
+    assert_eq!(error.to_string(), "can't tell weekday of day \"?\"");
+}
+
+#[test]
+fn schema_checker_reports_and_repairs_drift() {
+    let response = serde_json::json!({
+        "EducatorLongDisplayText": "Энди Уорхол",
+        "EducatorMasterId": "1928",
+        "EducatorEventsDays": [{
+            "DayString": "Понедельник",
+            "DayStudyEvents": [
+                {
+                    "Start": "08:30",
+                    "End": "10:00",
+                    "TimeIntervalString": "08:30-10:00",
+                    "EventLocations": [],
+                    "IsOnline": true
+                },
+                {
+                    "Start": "в полдень",
+                    "End": "13:30",
+                    "Subject": "Истоки поп-арта",
+                    "TimeIntervalString": "12:00–13:30",
+                    "EventLocations": []
+                }
+            ]
+        }]
+    })
+    .to_string();
+
+    let strict = SchemaChecker::new(false);
+    let error = strict
+        .parse::<EducatorEvents>("educators/{id}/events", response.as_bytes())
+        .unwrap_err();
+    assert_eq!(
+        error,
+        "missing field `Subject` at EducatorEventsDays[0].DayStudyEvents[0]"
+    );
+    let report = strict.take_report();
+    assert_eq!(
+        report.endpoints["educators/{id}/events"]
+            .iter()
+            .map(|issue| (issue.kind, issue.path.as_str()))
+            .collect::<Vec<_>>(),
+        vec![(
+            SchemaIssueKind::Missing,
+            "EducatorEventsDays[].DayStudyEvents[].Subject"
+        )]
+    );
+    assert!(strict.take_report().is_empty());
+
+    let lenient = SchemaChecker::new(true);
+    let educator = lenient
+        .parse::<EducatorEvents>("educators/{id}/events", response.as_bytes())
+        .unwrap();
+    assert_eq!(educator.educator_master_id, 1928);
+    let events = &educator.educator_events_days[&DayOfWeek::Monday].day_study_events;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events.first().unwrap().subject, "");
+    let report = lenient.take_report();
+    assert_eq!(
+        report.endpoints["educators/{id}/events"]
+            .iter()
+            .map(|issue| (issue.kind, issue.path.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                SchemaIssueKind::Unknown,
+                "EducatorEventsDays[].DayStudyEvents[].IsOnline"
+            ),
+            (
+                SchemaIssueKind::Missing,
+                "EducatorEventsDays[].DayStudyEvents[].Subject"
+            ),
+            (SchemaIssueKind::Retyped, "EducatorMasterId"),
+            (
+                SchemaIssueKind::Dropped,
+                "EducatorEventsDays[].DayStudyEvents[]"
+            ),
+        ]
+    );
+
+    let email =
+        generate_schema_report_email(&get_test_config(), "admin@example.com", &report).unwrap();
+    let formatted = email.formatted();
+    let parsed = mailparse::parse_mail(&formatted).unwrap();
+    assert!(parsed.subparts[0].get_body().unwrap().contains(
+        "educators/{id}/events:\r\n    Неизвестное поле EducatorEventsDays[].DayStudyEvents[].IsOnline\r\n"
+    ));
+    assert!(parsed.subparts[1].get_body().unwrap().contains(
+        "<b>educators/{id}/events</b>:<br>    Неизвестное поле EducatorEventsDays[].DayStudyEvents[].IsOnline<br>"
+    ));
+    /* details leave values out, so responses of every educator make the same issues */
+    assert!(parsed.subparts[1]
+        .get_body()
+        .unwrap()
+        .contains("EducatorMasterId: invalid type: string, expected u32<br>"));
+    assert!(parsed.subparts[1].get_body().unwrap().contains(
+        "EducatorEventsDays[].DayStudyEvents[]: invalid event time, expected HH:MM, HH:MM:SS or YYYY-MM-DDTHH:MM:SS<br>"
+    ));
+    let other_educator = response
+        .replace("1928", "1879")
+        .replace("в полдень", "после обеда");
+    lenient
+        .parse::<EducatorEvents>("educators/{id}/events", response.as_bytes())
+        .unwrap();
+    lenient
+        .parse::<EducatorEvents>("educators/{id}/events", other_educator.as_bytes())
+        .unwrap();
+    assert_eq!(lenient.take_report(), report);
+}
+
+#[test]
+fn user_filters_drop_unwanted_changes() {
+    let args_old = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let args_new = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.many_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let monday = NaiveDate::from_ymd_opt(2024, 10, 14);
+    let date_events = |events: BTreeMap<ScheduleKey, EducatorEvents>| {
+        events
+            .into_values()
+            .map(|mut educator| {
+                educator.week_start = monday;
+                (educator.key(), educator)
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+    let old = date_events(get_previous_events(&args_old).unwrap().educators);
+    let new = date_events(get_previous_events(&args_new).unwrap().educators);
+    let changed = ChangedSchedules {
+        educators: generate_diffs(&old, &new),
+        ..Default::default()
+    };
+    let mut users = get_users(&args_old).unwrap();
+    let tuesday = NaiveDate::from_ymd_opt(2024, 10, 15).unwrap();
+    let subjects = |user: &User| {
+        let filtered = filter_changes(&changed, user, tuesday);
+        let mut subjects = Vec::new();
+        for (_, diff) in filtered.educators.values() {
+            for day in diff.days.values() {
+                match &day.change {
+                    DayChange::Added { events } | DayChange::Removed { events } => {
+                        subjects.extend(events.iter().map(|event| event.subject.clone()))
+                    }
+                    DayChange::Modified(events) => {
+                        subjects.extend(
+                            events
+                                .modified
+                                .iter()
+                                .map(|event| event.new.subject.clone()),
+                        );
+                        subjects.extend(events.added.iter().map(|event| event.subject.clone()));
+                    }
+                }
+            }
+        }
+        subjects
+    };
+    assert_eq!(subjects(&users[0]).len(), 5);
+
+    users[0].filters.groups = vec!["группа 102b".to_string()];
+    assert_eq!(subjects(&users[0]), vec!["Истоки поп-арта"]);
+
+    users[0].filters = Filters {
+        exclude_subjects: vec!["поп-арт".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        subjects(&users[0]),
+        vec![
+            "От кубизма к супрематизму",
+            "Декларация прав художника",
+            "Как превратить искусство в массовый продукт"
+        ]
+    );
+
+    users[0].filters = Filters {
+        include_subjects: vec!["Супрематизм".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(subjects(&users[0]), vec!["От кубизма к супрематизму"]);
+
+    /* Monday has passed and Wednesday is too far */
+    users[0].filters = Filters {
+        days_ahead: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(
+        subjects(&users[0]),
+        vec!["От кубизма к супрематизму", "Декларация прав художника"]
+    );
+
+    /* a period past the last representable date leaves out only Monday, which has passed */
+    users[0].filters = Filters {
+        days_ahead: Some(u64::MAX),
+        ..Default::default()
+    };
+    assert_eq!(subjects(&users[0]).len(), 3);
+
+    /* no letter for a user whose changes were all filtered out */
+    users[0].filters = Filters {
+        groups: vec!["Группа 999".to_string()],
+        ..Default::default()
+    };
+    let filtered = filter_changes(&changed, &users[0], tuesday);
+    assert!(filtered.educators.is_empty());
+    assert_eq!(collect_all_tracked_diffs(&filtered, &users[0]), "");
+}
+
+#[test]
+fn changes_are_reported_once_confirmed() {
+    let args_old = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let args_new = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.many_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let mut config = get_test_config();
+    config.confirm_after_runs = Some(2);
+    let start = "2024-10-14T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+    let hour = chrono::Duration::hours(1);
+    let old = get_previous_events(&args_old).unwrap();
+
+    /* the first run holds the change back */
+    let mut first = get_previous_events(&args_new).unwrap();
+    debounce_changes(&config, start, &old, &mut first);
+    assert_eq!(first.educators, old.educators);
+    assert_eq!(first.pending.educators[&(1928, None)].runs, 1);
+    assert!(generate_all_diffs(&old, &first, NaiveDate::MIN)
+        .educators
+        .is_empty());
+
+    /* the second one reports it */
+    let mut second = get_previous_events(&args_new).unwrap();
+    debounce_changes(&config, start + hour, &first, &mut second);
+    assert!(second.pending.is_empty());
+    assert_eq!(
+        generate_all_diffs(&first, &second, NaiveDate::MIN)
+            .educators
+            .len(),
+        2
+    );
+
+    /* a change which was rolled back is never reported */
+    let mut rolled_back = get_previous_events(&args_old).unwrap();
+    debounce_changes(&config, start + hour, &first, &mut rolled_back);
+    assert!(rolled_back.pending.is_empty());
+    assert!(generate_all_diffs(&first, &rolled_back, NaiveDate::MIN)
+        .educators
+        .is_empty());
+
+    /* pending changes of schedules which failed to fetch wait for the next run */
+    let mut failed = Schedule::default();
+    let failures = [FetchFailure {
+        schedule: ScheduleRef::Educator((1928, None)),
+        error: "timeout".to_string(),
+    }];
+    carry_forward_failed(&first, &mut failed, &failures);
+    debounce_changes(&config, start + hour, &first, &mut failed);
+    assert_eq!(
+        failed.educators[&(1928, None)],
+        first.educators[&(1928, None)]
+    );
+    assert_eq!(failed.pending.educators[&(1928, None)].runs, 1);
+
+    /* a time window confirms changes which were fetched only once */
+    config.confirm_after_runs = None;
+    config.confirm_after_minutes = Some(90);
+    let mut early = get_previous_events(&args_new).unwrap();
+    debounce_changes(&config, start + hour, &first, &mut early);
+    assert_eq!(early.pending.len(), 2);
+    let mut late = get_previous_events(&args_new).unwrap();
+    debounce_changes(&config, start + hour * 2, &first, &mut late);
+    assert!(late.pending.is_empty());
+}
+
+#[test]
+fn events_moved_between_educators_are_reassignments() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let old = get_previous_events(&args).unwrap();
+    let mut new = get_previous_events(&args).unwrap();
+    let warhol_monday = new
+        .educators
+        .get_mut(&(1928, None))
+        .unwrap()
+        .educator_events_days
+        .get_mut(&DayOfWeek::Monday)
+        .unwrap();
+    let mut event = warhol_monday.day_study_events.pop_first().unwrap();
+    event.event_locations = BTreeSet::from([EventLocation {
+        display_name: "Дворцовая площадь, д. 6/8".to_string(),
+    }]);
+    new.educators
+        .get_mut(&(1879, None))
+        .unwrap()
+        .educator_events_days
+        .get_mut(&DayOfWeek::Monday)
+        .unwrap()
+        .day_study_events
+        .insert(event.clone());
+
+    let changed = generate_all_diffs(&old, &new, NaiveDate::MIN);
+    let reassignment = Reassignment {
+        event,
+        from: "Энди Уорхол".to_string(),
+        to: "Казимир Малевич".to_string(),
+    };
+    for key in [(1928, None), (1879, None)] {
+        let day = &changed.educators[&key].1.days[&DayOfWeek::Monday];
+        assert_eq!(
+            day.change,
+            DayChange::Modified(EventsDiff {
+                reassigned: vec![reassignment.clone()],
+                ..Default::default()
+            })
+        );
+    }
+
+    let mut users = get_users(&args).unwrap();
+    users[0].watch_educators = BTreeSet::from([1879]);
+    assert_eq!(collect_all_tracked_diffs(&changed, &users[0]), "В расписании преподавателя <b>Казимир Малевич</b> произошли изменения:<br><br><b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:orange;\">Замена: Энди Уорхол → Казимир Малевич:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 101A<br><br>");
+}
+
+#[test]
+fn changes_of_past_dates_are_ignored() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let old = get_previous_events(&args).unwrap();
+    let date = |day| NaiveDate::from_ymd_opt(1963, 9, day).unwrap();
+    let today = date(5);
+    let with_dates = |dates: &[NaiveDate]| {
+        let mut new = get_previous_events(&args).unwrap();
+        let events = &mut new
+            .educators
+            .get_mut(&(1928, None))
+            .unwrap()
+            .educator_events_days
+            .get_mut(&DayOfWeek::Monday)
+            .unwrap()
+            .day_study_events;
+        let mut event = events.pop_first().unwrap();
+        event.dates.extend(dates);
+        events.insert(event);
+        new
+    };
+
+    let past = with_dates(&[date(2)]);
+    assert!(generate_all_diffs(&old, &past, today).educators.is_empty());
+    assert_eq!(
+        generate_all_diffs(&old, &past, NaiveDate::MIN)
+            .educators
+            .len(),
+        1
+    );
+
+    let both = with_dates(&[date(2), date(15)]);
+    let changed = generate_all_diffs(&old, &both, today);
+    let DayChange::Modified(day) =
+        &changed.educators[&(1928, None)].1.days[&DayOfWeek::Monday].change
+    else {
+        panic!("Monday should be modified");
+    };
+    assert_eq!(day.modified.len(), 1);
+    assert_eq!(day.modified[0].added_dates, BTreeSet::from([date(15)]));
+    assert!(day.modified[0].removed_dates.is_empty());
+}
+
+#[test]
+fn changes_of_past_days_of_group_schedules_are_ignored() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.group.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let old = get_previous_events(&args).unwrap();
+    let key = (394847, NaiveDate::from_ymd_opt(2024, 10, 14));
+    /* group events have no `Dates`, Monday's lecture is dated by the week of the schedule */
+    let mut new = get_previous_events(&args).unwrap();
+    let events = &mut new
+        .groups
+        .get_mut(&key)
+        .unwrap()
+        .days
+        .get_mut(&DayOfWeek::Monday)
+        .unwrap()
+        .day_study_events;
+    let lecture = events.pop_first().unwrap();
+    assert!(lecture.dates.is_empty());
+    events.insert(DayStudyEvent {
+        event_locations: BTreeSet::new(),
+        ..lecture
+    });
+    let monday = NaiveDate::from_ymd_opt(2024, 10, 14).unwrap();
+    let wednesday = NaiveDate::from_ymd_opt(2024, 10, 16).unwrap();
+
+    assert_eq!(generate_all_diffs(&old, &new, monday).groups.len(), 1);
+    assert!(generate_all_diffs(&old, &new, wednesday).groups.is_empty());
+
+    let mut emptied = get_previous_events(&args).unwrap();
+    emptied.groups.get_mut(&key).unwrap().days.clear();
+    assert_eq!(generate_all_diffs(&old, &emptied, monday).groups.len(), 1);
+    assert!(generate_all_diffs(&old, &emptied, wednesday)
+        .groups
+        .is_empty());
+}
+
+#[test]
+fn events_which_are_over_are_neither_added_nor_removed() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let old = get_previous_events(&args).unwrap();
+    let date = |day| NaiveDate::from_ymd_opt(1963, 9, day).unwrap();
+    /* the only lecture of 01.09 is moved from Monday to Saturday, possibly with Monday itself */
+    let moved = |drop_monday: bool| {
+        let mut new = get_previous_events(&args).unwrap();
+        let days = &mut new
+            .educators
+            .get_mut(&(1928, None))
+            .unwrap()
+            .educator_events_days;
+        let monday = days.get_mut(&DayOfWeek::Monday).unwrap();
+        let lecture = monday.day_study_events.pop_first().unwrap();
+        if drop_monday {
+            days.remove(&DayOfWeek::Monday);
+        }
+        days.get_mut(&DayOfWeek::Saturday)
+            .unwrap()
+            .day_study_events
+            .insert(DayStudyEvent {
+                subject: "Истоки поп-арта".to_string(),
+                ..lecture
+            });
+        new
+    };
+
+    for drop_monday in [false, true] {
+        let new = moved(drop_monday);
+        let changed = generate_all_diffs(&old, &new, date(1));
+        assert_eq!(changed.educators[&(1928, None)].1.days.len(), 2);
+        assert!(generate_all_diffs(&old, &new, date(2)).educators.is_empty());
+    }
+    let without_monday = moved(true);
+    let changed = generate_all_diffs(&old, &without_monday, date(1));
+    assert!(matches!(
+        changed.educators[&(1928, None)].1.days[&DayOfWeek::Monday].change,
+        DayChange::Removed { .. }
+    ));
+}
+
+#[test]
+fn new_semester_is_reported_with_a_notice() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let mut old = get_previous_events(&args).unwrap();
+    let warhol = old.educators.get_mut(&(1928, None)).unwrap();
+    let monday = warhol
+        .educator_events_days
+        .get_mut(&DayOfWeek::Monday)
+        .unwrap();
+    let lecture = monday.day_study_events.first().unwrap().clone();
+    monday.day_study_events = (1..=6)
+        .map(|number| DayStudyEvent {
+            subject: format!("Лекция {}", number),
+            ..lecture.clone()
+        })
+        .collect();
+    let with_events = |change: &dyn Fn(DayStudyEvent) -> DayStudyEvent, count: usize| {
+        let mut new = Schedule {
+            educators: old.educators.clone(),
+            ..Default::default()
+        };
+        let events = &mut new
+            .educators
+            .get_mut(&(1928, None))
+            .unwrap()
+            .educator_events_days
+            .get_mut(&DayOfWeek::Monday)
+            .unwrap()
+            .day_study_events;
+        let changed = (0..count)
+            .map(|_| change(events.pop_first().unwrap()))
+            .collect::<Vec<_>>();
+        events.extend(changed);
+        new
+    };
+
+    let renamed = with_events(
+        &|event| DayStudyEvent {
+            subject: format!("{} (весна)", event.subject),
+            ..event
+        },
+        5,
+    );
+    let changed = generate_all_diffs(&old, &renamed, NaiveDate::MIN);
+    assert_eq!(
+        changed.educators[&(1928, None)].1,
+        ScheduleDiff::new_semester()
+    );
+    let users = get_users(&args).unwrap();
+    assert_eq!(
+        collect_all_tracked_diffs(&changed, &users[0]),
+        "Опубликовано расписание преподавателя <b>Энди Уорхол</b> на новый семестр.<br>"
+    );
+
+    /* weeks of a weekly schedule get a single notice */
+    let weeks = [
+        NaiveDate::from_ymd_opt(1963, 9, 2).unwrap(),
+        NaiveDate::from_ymd_opt(1963, 9, 9).unwrap(),
+    ];
+    let weekly = |schedule: &Schedule| Schedule {
+        educators: weeks
+            .iter()
+            .map(|week| {
+                let events = EducatorEvents {
+                    week_start: Some(*week),
+                    ..schedule.educators[&(1928, None)].clone()
+                };
+                (events.key(), events)
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let (weekly_old, weekly_renamed) = (weekly(&old), weekly(&renamed));
+    let changed = generate_all_diffs(&weekly_old, &weekly_renamed, NaiveDate::MIN);
+    assert_eq!(
+        changed.educators.keys().collect::<Vec<_>>(),
+        vec![&(1928, None)]
+    );
+    assert_eq!(
+        collect_all_tracked_diffs(&changed, &users[0]),
+        "Опубликовано расписание преподавателя <b>Энди Уорхол</b> на новый семестр.<br>"
+    );
+
+    let moved = with_events(
+        &|event| DayStudyEvent {
+            dates: event
+                .dates
+                .iter()
+                .map(|date| *date + chrono::Days::new(180))
+                .collect(),
+            ..event
+        },
+        6,
+    );
+    let changed = generate_all_diffs(&old, &moved, NaiveDate::MIN);
+    assert!(changed.educators[&(1928, None)].1.new_semester);
+
+    let one_renamed = with_events(
+        &|event| DayStudyEvent {
+            subject: format!("{} (весна)", event.subject),
+            ..event
+        },
+        1,
+    );
+    let changed = generate_all_diffs(&old, &one_renamed, NaiveDate::MIN);
+    let diff = &changed.educators[&(1928, None)].1;
+    assert!(!diff.new_semester);
+    assert_eq!(diff.days.len(), 1);
+
+    /* a week emptied e.g. by holidays has nothing new in it, so its removals are reported */
+    let mut emptied = Schedule {
+        educators: old.educators.clone(),
+        ..Default::default()
+    };
+    for day in emptied
+        .educators
+        .get_mut(&(1928, None))
+        .unwrap()
+        .educator_events_days
+        .values_mut()
+    {
+        day.day_study_events.clear();
+    }
+    let changed = generate_all_diffs(&old, &emptied, NaiveDate::MIN);
+    let diff = &changed.educators[&(1928, None)].1;
+    assert!(!diff.new_semester);
+    let DayChange::Modified(monday) = &diff.days[&DayOfWeek::Monday].change else {
+        panic!("emptied day is still in the schedule");
+    };
+    assert_eq!(monday.removed.len(), 6);
+}
+
+#[test]
+fn templates_from_config_replace_built_in_ones() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let users = get_users(&args).unwrap();
+    let old = get_previous_events(&args).unwrap();
+    let new = get_previous_events(&Args {
+        previous_events_json_path: PathBuf::from("tests/test.many_events.json"),
+        ..args
+    })
+    .unwrap();
+    let changed = generate_all_diffs(&old, &new, NaiveDate::MIN);
+
+    let paths = TemplatePaths {
+        changes: Some(PathBuf::from("tests/test.changes_template.html")),
+        ..Default::default()
+    };
+    let letters = Letters::new(&paths).unwrap();
+    let letter = letters
+        .changes_letter(&changed, &users[0])
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        letter.html,
+        "Энди Уорхол:\n1879 Казимир Малевич 1\n1928 Энди Уорхол 2\n"
+    );
+    /* the plain-text letter has a template of its own */
+    assert!(letter
+        .text
+        .starts_with("Уважаемый(ая) Энди Уорхол!\n\nВ расписании преподавателя Казимир Малевич"));
+    assert!(letters
+        .changes_letter(&ChangedSchedules::default(), &users[0])
+        .unwrap()
+        .is_none());
+
+    /* letters not replaced in config stay built-in */
+    let subscriptions = Subscriptions {
+        educators: BTreeSet::from([1879]),
+        ..Default::default()
+    };
+    let welcome = letters
+        .welcome_letter(&new, &subscriptions, &users[0])
+        .unwrap();
+    assert!(welcome
+        .html
+        .starts_with("Уважаемый(ая) Энди Уорхол!<br><br> Вы подписались на оповещения"));
+    assert!(welcome
+        .text
+        .starts_with("Уважаемый(ая) Энди Уорхол!\n\nВы подписались на оповещения"));
+
+    let missing = TemplatePaths {
+        macros: Some(PathBuf::from("tests/missing_template.html")),
+        ..Default::default()
+    };
+    assert!(Letters::new(&missing).is_err());
+}
+
+#[test]
+fn letters_are_written_in_the_users_language() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let mut users = get_users(&args).unwrap();
+    let old = get_previous_events(&args).unwrap();
+    let new = get_previous_events(&Args {
+        previous_events_json_path: PathBuf::from("tests/test.many_events.json"),
+        ..args
+    })
+    .unwrap();
+    let changed = generate_all_diffs(&old, &new, NaiveDate::MIN);
+    let letters = Letters::default();
+
+    /* users without a locale get Russian letters */
+    assert_eq!(users[0].locale, Locale::Ru);
+    let russian = letters
+        .changes_letter(&changed, &users[0])
+        .unwrap()
+        .unwrap();
+    assert!(russian
+        .html
+        .starts_with("Уважаемый(ая) Энди Уорхол!<br><br> В расписании преподавателя"));
+
+    let english: User = serde_json::from_str(
+        r#"{"name": "Энди Уорхол", "watch_educators": [1879, 1928], "watch_groups": [],
+            "email": "campbellsoupthebest@gmail.com", "locale": "en"}"#,
+    )
+    .unwrap();
+    assert_eq!(english.locale, Locale::En);
+    users[0] = english;
+    let letter = letters
+        .changes_letter(&changed, &users[0])
+        .unwrap()
+        .unwrap();
+    assert!(letter.html.starts_with("Dear Энди Уорхол!<br><br> The schedule of educator <b>Казимир Малевич</b> has changed:<br><br><b><font size=\"5\">Tuesday:</font></b><br><em style=\"color:orange;\">Changed events:</em><br>    <b>Subject:</b> От кубизма к супрематизму<br>    <b>Time:</b> 09:00-10:30<br>    <b>Dates:</b> added 22.12.1915<br>"));
+    assert!(letter
+        .text
+        .contains("New day:\nWednesday:\n    Subject: Истоки поп-арта\n"));
+    assert!(letter
+        .text
+        .ends_with("This letter was generated automatically, please do not reply to it."));
+
+    let email = generate_email(&get_test_config(), &users[0], &letter).unwrap();
+    let formatted = email.formatted();
+    let parsed = mailparse::parse_mail(&formatted).unwrap();
+    assert_eq!(
+        parsed.headers.get_first_value("Subject").unwrap(),
+        "Your schedule has changed!"
+    );
+}
+
+#[test]
+fn weekly_grid_is_shown_to_users_who_asked_for_it() {
+    let args = Args {
+        users_json_path: PathBuf::from("tests/test.users.json"),
+        config_json_path: PathBuf::from("tests/test.config.json"),
+        previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
+    };
+    let mut users = get_users(&args).unwrap();
+    let old = get_previous_events(&args).unwrap();
+    let new = get_previous_events(&Args {
+        previous_events_json_path: PathBuf::from("tests/test.many_events.json"),
+        ..args
+    })
+    .unwrap();
+    let changed = generate_all_diffs(&old, &new, NaiveDate::MIN);
+    let letters = Letters::default();
+
+    let letter = letters
+        .changes_letter(&changed, &users[0])
+        .unwrap()
+        .unwrap();
+    assert!(!letter.html.contains("<table"));
+
+    users[0].weekly_grid = true;
+    let letter = letters
+        .changes_letter(&changed, &users[0])
+        .unwrap()
+        .unwrap();
+    let cell = "border:1px solid #ccc;padding:4px;vertical-align:top;";
+    assert!(letter.html.contains(&format!("В расписании преподавателя <b>Энди Уорхол</b> произошли изменения:<br><br><table style=\"border-collapse:collapse;\"><tr><th style=\"{cell}\"></th><th style=\"{cell}\">Понедельник</th><th style=\"{cell}\">Среда</th></tr><tr><td style=\"{cell}white-space:nowrap;\">08:30-10:00</td><td style=\"{cell}\"><span style=\"color:orange;\">Как превратить искусство в массовый продукт</span></td><td style=\"{cell}\"></td></tr><tr><td style=\"{cell}white-space:nowrap;\">10:15-11:45</td><td style=\"{cell}\"><span style=\"color:green;background-color:#e6f4e6;\">Истоки поп-арта</span></td><td style=\"{cell}\"></td></tr><tr><td style=\"{cell}white-space:nowrap;\">13:00-14:30</td><td style=\"{cell}\"></td><td style=\"{cell}\"><span style=\"color:green;background-color:#e6f4e6;\">Истоки поп-арта</span></td></tr></table><br><b><font size=\"5\">Понедельник:</font></b>")));
+    /* the list of changes stays below the table, plain text has no table */
+    assert!(letter
+        .html
+        .contains("<em style=\"color:green;\">Новый день:</em>"));
+    assert!(!letter.text.contains("<table"));
 }
@@ -1,23 +1,25 @@
 use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+use chrono::{DateTime, NaiveDate, Utc};
 use figment::providers::Env;
 use figment::providers::Format;
 use figment::providers::Json;
 use figment::Figment;
 use lettre::address::Envelope;
-use lettre::message::header::ContentType;
+use lettre::message::MultiPart;
 use lettre::transport::stub::StubTransport;
 use lettre::Address;
 use lettre::Message;
 use lettre::Transport;
-use lib::tt_diff::helpers::collect_all_tracked_diffs;
 use lib::tt_diff::helpers::generate_email;
 use lib::tt_diff::helpers::get_previous_events;
+use lib::tt_diff::helpers::FetchError;
 use lib::tt_diff::letter_sender::LetterSender;
-use lib::tt_diff::models::educator_model::EducatorEvents;
+use lib::tt_diff::models::educator_model::FoundEducator;
 use lib::tt_diff::models::Args;
-use lib::tt_diff::models::{Config, User};
+use lib::tt_diff::models::{ChangedSchedules, Config, FetchReport, Schedule, SchemaReport, User};
+use lib::tt_diff::render::{Letter, Letters};
 use lib::tt_diff::run_tool::run;
 use lib::tt_diff::schedule_getter::ScheduleGetter;
 use mailparse::parse_mail;
@@ -27,14 +29,30 @@ pub struct TestGetter {
 }
 
 impl ScheduleGetter for TestGetter {
-    async fn get_schedule(&self, _users: &Vec<User>) -> BTreeMap<u32, EducatorEvents> {
+    async fn get_schedule(
+        &self,
+        _users: &[User],
+        _config: &Config,
+        _now: DateTime<Utc>,
+    ) -> (Schedule, FetchReport) {
         let mock_site = Args {
             users_json_path: PathBuf::from("_"),
             config_json_path: PathBuf::from("_"),
             previous_events_json_path: PathBuf::from(self.new_schedule_path.clone()),
+            schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
         };
-        let new_schedule = get_previous_events(&mock_site).unwrap();
-        return new_schedule;
+        (
+            get_previous_events(&mock_site).unwrap(),
+            FetchReport::default(),
+        )
+    }
+
+    async fn search_educators(
+        &self,
+        _name: &str,
+        _config: &Config,
+    ) -> Result<Vec<FoundEducator>, FetchError> {
+        Ok(Vec::new())
     }
 }
 
@@ -53,25 +71,40 @@ pub fn assert_emails_eq(fst: &Message, snd: &Message) {
     let fst_readable = parse_mail(&fst_raw).unwrap();
     let snd_readable = parse_mail(&snd_raw).unwrap();
 
-    assert_eq!(
-        fst_readable.get_body().unwrap(),
-        snd_readable.get_body().unwrap()
-    );
+    assert_eq!(fst_readable.ctype.mimetype, "multipart/alternative");
+    assert_eq!(fst_readable.subparts.len(), 2);
+    assert_eq!(fst_readable.subparts[0].ctype.mimetype, "text/plain");
+    assert_eq!(fst_readable.subparts[1].ctype.mimetype, "text/html");
+    for (fst_part, snd_part) in fst_readable.subparts.iter().zip(&snd_readable.subparts) {
+        assert_eq!(fst_part.ctype.mimetype, snd_part.ctype.mimetype);
+        assert_eq!(fst_part.get_body().unwrap(), snd_part.get_body().unwrap());
+    }
+}
+
+// boundaries of multipart letters are random and the date depends on when a letter was built,
+// so they are dropped before comparing serialised letters
+pub fn without_boundary(raw: &[u8]) -> String {
+    let boundary = parse_mail(raw).unwrap().ctype.params["boundary"].clone();
+    String::from_utf8(raw.to_vec())
+        .unwrap()
+        .replace(&boundary, "BOUNDARY")
+        .split("\r\n")
+        .filter(|line| !line.starts_with("Date: "))
+        .collect::<Vec<_>>()
+        .join("\r\n")
 }
 
 impl LetterSender for TestSender {
     fn form_and_send_letters(
         self,
-        users: Vec<User>,
         config: Config,
-        ed_changed: BTreeMap<u32, (&EducatorEvents, String)>,
+        letters: &Letters,
+        changes: Vec<(User, ChangedSchedules)>,
     ) {
-        for user in users.iter() {
+        for (user, changed) in changes.iter() {
             let user_id = &user.email;
-            let diff = collect_all_tracked_diffs(&ed_changed, user);
-
-            if diff.len() > 0 {
-                let email = generate_email(&config, user, &diff).unwrap();
+            if let Some(letter) = letters.changes_letter(changed, user).unwrap() {
+                let email = generate_email(&config, user, &letter).unwrap();
                 let _ = self.transport.send(&email);
                 let expected_email = self
                     .expected
@@ -84,26 +117,30 @@ impl LetterSender for TestSender {
                 // assert body in readable format
                 assert_emails_eq(&email, &expected_email);
                 // assert headers and serialised letter contents
+                let messages = self
+                    .transport
+                    .messages()
+                    .into_iter()
+                    .map(|(envelope, raw)| (envelope, without_boundary(raw.as_bytes())))
+                    .collect::<Vec<_>>();
                 assert_eq!(
-                    self.transport.messages(),
-                    vec![
-                        ((
-                            self.expected
-                                .get(user_id)
-                                .unwrap()
-                                .as_ref()
-                                .unwrap()
-                                .0
-                                .clone(),
-                            self.expected
-                                .get(user_id)
-                                .unwrap()
-                                .as_ref()
-                                .unwrap()
-                                .1
-                                .clone()
-                        ))
-                    ],
+                    messages,
+                    vec![(
+                        self.expected
+                            .get(user_id)
+                            .unwrap()
+                            .as_ref()
+                            .unwrap()
+                            .0
+                            .clone(),
+                        self.expected
+                            .get(user_id)
+                            .unwrap()
+                            .as_ref()
+                            .unwrap()
+                            .1
+                            .clone()
+                    )],
                 )
             } else {
                 // indicates that if diff length is 0, then there is no letter in the expected
@@ -111,14 +148,66 @@ impl LetterSender for TestSender {
             }
         }
     }
+
+    fn send_welcome_letter(&self, _config: &Config, _user: &User, _letter: &Letter) {
+        panic!("subscriptions of test users are unknown, so none of them is new");
+    }
+
+    fn send_schema_report(&self, _config: &Config, _report: &SchemaReport) {
+        panic!("test responses match the model");
+    }
 }
 
+const WARHOL_TEXT: &str = "Уважаемый(ая) Энди Уорхол!
+
+В расписании преподавателя Казимир Малевич произошли изменения:
+
+Вторник:
+Изменённые события:
+    Предмет: От кубизма к супрематизму
+    Время: 09:00-10:30
+    Даты: добавлены 22.12.1915
+
+Новые события:
+    Предмет: Декларация прав художника
+    Время: 11:00-12:30
+    Даты: 15.08.1918, 22.08.1918
+    Места: Дворцовая площадь, д. 6/8
+    Направления: Группа 202A
+
+В расписании преподавателя Энди Уорхол произошли изменения:
+
+Понедельник:
+Изменённые события:
+    Предмет: Как превратить искусство в массовый продукт
+    Время: 08:30-10:00
+    Даты: добавлены 08.09.1963
+    Направления: Группа 101A → Группа 101A, Группа 101B
+
+Новые события:
+    Предмет: Истоки поп-арта
+    Время: 10:15-11:45
+    Даты: 01.09.1968, 08.09.1968
+    Места: 33 Union Square West
+    Направления: Группа 102B
+
+Новый день:
+Среда:
+    Предмет: Истоки поп-арта
+    Время: 13:00-14:30
+    Даты: 02.09.1968, 10.09.1968
+    Места: 33 Union Square West
+    Направления: Группа 103C
+
+Данное письмо было сгенерировано автоматически, направление ответа не подразумевается.";
+
 #[tokio::test]
 async fn test_main() {
     let args = Args {
         users_json_path: PathBuf::from("tests/test.users.json"),
         config_json_path: PathBuf::from("example.config.json"),
         previous_events_json_path: PathBuf::from("tests/test.less_events.json"),
+        schema_report_json_path: PathBuf::from("tests/test.schema_report.json"),
     };
     let config: Config = Figment::new()
         .merge(Json::file(&args.config_json_path))
@@ -143,11 +232,10 @@ async fn test_main() {
     let warhol_email = Message::builder()
     .from(format!("{} <{}>", config.email_sender_fullname, config.email_sender_username).parse().unwrap())
     .to("Энди Уорхол <campbellsoupthebest@gmail.com>".parse().unwrap())
-    .subject("Изменилось расписание преподавателя!")
-    .header(ContentType::TEXT_HTML)
-    .body(String::from("Уважаемый(ая) Энди Уорхол!<br><br> В расписании преподавателя <b>Казимир Малевич</b> произошли изменения:<br><br><b><font size=\"5\">Вторник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> 22.12.1915, 29.12.1915<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 201A, Группа 201B<br><br>    <b>Предмет:</b> Декларация прав художника<br>    <b>Время:</b> 11:00-12:30<br>    <b>Даты:</b> 15.08.1918, 22.08.1918<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 202A<br><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> 29.12.1915<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 201A, Группа 201B<br><br><br> <br>В расписании преподавателя <b>Энди Уорхол</b> произошли изменения:<br><br><b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963, 08.09.1963<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 101A, Группа 101B<br><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 10:15-11:45<br>    <b>Даты:</b> 01.09.1968, 08.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 102B<br><br><em style=\"color:red;\">Удалённые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> 01.09.1963<br>    <b>Места:</b> 231 East 47th Street<br>    <b>Направления:</b> Группа 101A<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Среда:</font></b><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br><br> <br> Данное письмо было сгенерировано автоматически, направление ответа не подразумевается.")).unwrap();
+    .subject("Изменилось расписание!")
+    .multipart(MultiPart::alternative_plain_html(String::from(WARHOL_TEXT), String::from("Уважаемый(ая) Энди Уорхол!<br><br> В расписании преподавателя <b>Казимир Малевич</b> произошли изменения:<br><br><b><font size=\"5\">Вторник:</font></b><br><em style=\"color:orange;\">Изменённые события:</em><br>    <b>Предмет:</b> От кубизма к супрематизму<br>    <b>Время:</b> 09:00-10:30<br>    <b>Даты:</b> добавлены 22.12.1915<br><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Декларация прав художника<br>    <b>Время:</b> 11:00-12:30<br>    <b>Даты:</b> 15.08.1918, 22.08.1918<br>    <b>Места:</b> Дворцовая площадь, д. 6/8<br>    <b>Направления:</b> Группа 202A<br><br><br> <br>В расписании преподавателя <b>Энди Уорхол</b> произошли изменения:<br><br><b><font size=\"5\">Понедельник:</font></b><br><em style=\"color:orange;\">Изменённые события:</em><br>    <b>Предмет:</b> Как превратить искусство в массовый продукт<br>    <b>Время:</b> 08:30-10:00<br>    <b>Даты:</b> добавлены 08.09.1963<br>    <b>Направления:</b> Группа 101A → Группа 101A, Группа 101B<br><br><em style=\"color:green;\">Новые события:</em><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 10:15-11:45<br>    <b>Даты:</b> 01.09.1968, 08.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 102B<br><br><em style=\"color:green;\">Новый день:</em><br><b><font size=\"5\">Среда:</font></b><br>    <b>Предмет:</b> Истоки поп-арта<br>    <b>Время:</b> 13:00-14:30<br>    <b>Даты:</b> 02.09.1968, 10.09.1968<br>    <b>Места:</b> 33 Union Square West<br>    <b>Направления:</b> Группа 103C<br><br> <br> Данное письмо было сгенерировано автоматически, направление ответа не подразумевается."))).unwrap();
 
-    let warhol_contents = String::from_utf8(warhol_email.formatted()).unwrap();
+    let warhol_contents = without_boundary(&warhol_email.formatted());
 
     test_expected.insert(
         "campbellsoupthebest@gmail.com".to_string(),
@@ -160,5 +248,11 @@ async fn test_main() {
         expected: test_expected,
     };
 
-    let _ = run(test_getter, test_sender, &args, config).await;
+    // all events of the fixtures are in the past of today, so the clock is set before them
+    let now = NaiveDate::from_ymd_opt(1900, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc();
+    let _ = run(test_getter, test_sender, &args, config, now).await;
 }